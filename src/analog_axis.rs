@@ -1,5 +1,6 @@
 use crate::vector_math::Vector;
 
+#[derive(Clone)]
 pub struct AnalogAxis {
     pub value: f64,
     pub previous_value: f64,
@@ -36,7 +37,6 @@ impl AnalogAxis {
     }
     pub fn is_active(&self) -> bool { self.value.abs() >= self.dead_zone }
     pub fn just_activated(&self) -> bool { self.just_crossed_center() || self.is_active() && !self.was_previously_active }
-    pub fn just_deactivated(&self) -> bool { self.was_previously_active && !self.is_active() }
 
     pub fn set_value_from_states(&mut self, low: bool, high: bool) {
         if high && !low {
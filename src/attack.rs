@@ -0,0 +1,69 @@
+use serde::Deserialize;
+
+use crate::point_math::Point;
+
+// Frame data for an attack. Frames count from 0 like state frames do.
+#[derive(Clone)]
+pub struct Attack {
+    pub name: String,
+    pub kind: AttackKind,
+    pub total_frames: u32,
+    pub landing_lag: u32,
+    pub l_cancel_landing_lag: u32,
+    pub hitboxes: Vec<Hitbox>,
+}
+
+// Ground attacks are started with A while standing and aerials with A in the air.
+// Only aerials have landing lag.
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+pub enum AttackKind {
+    Ground,
+    Aerial,
+}
+
+// A circle that is active from its start frame through its end frame. The offset
+// is for a fighter facing right. Angles are in degrees going counter clockwise from
+// straight forward.
+#[derive(Clone)]
+pub struct Hitbox {
+    pub start_frame: u32,
+    pub end_frame: u32,
+    pub offset: Point,
+    pub radius: f64,
+    pub damage: f64,
+    pub angle: f64,
+    pub base_knockback: f64,
+    pub knockback_growth: f64,
+}
+
+// A capsule around a fighter's body, in stage coordinates.
+pub struct Hurtbox {
+    pub bottom: Point,
+    pub top: Point,
+    pub radius: f64,
+}
+
+impl Hurtbox {
+    // Whether a circle touches the capsule.
+    pub fn overlaps(&self, center: &Point, radius: f64) -> bool {
+        let axis_x = self.top.x - self.bottom.x;
+        let axis_y = self.top.y - self.bottom.y;
+        let axis_length_squared = axis_x * axis_x + axis_y * axis_y;
+        let fraction = if axis_length_squared > 0.0 {
+            (((center.x - self.bottom.x) * axis_x + (center.y - self.bottom.y) * axis_y) / axis_length_squared).clamp(0.0, 1.0)
+        }
+        else {
+            0.0
+        };
+        let closest = self.bottom.lerp(&self.top, fraction);
+        let distance = ((center.x - closest.x).powi(2) + (center.y - closest.y).powi(2)).sqrt();
+        distance <= radius + self.radius
+    }
+}
+
+impl Attack {
+    pub fn active_hitboxes(&self, frame: u32) -> impl Iterator<Item = &Hitbox> {
+        self.hitboxes.iter()
+            .filter(move |hitbox| frame >= hitbox.start_frame && frame <= hitbox.end_frame)
+    }
+}
@@ -1,3 +1,4 @@
+#[derive(Clone)]
 pub struct Button {
     pub is_pressed: bool,
     pub was_previously_pressed: bool,
@@ -15,10 +16,6 @@ impl Button {
         self.is_pressed && !self.was_previously_pressed
     }
 
-    pub fn just_released(&self) -> bool {
-        self.was_previously_pressed && !self.is_pressed
-    }
-
     pub fn update(&mut self) {
         self.was_previously_pressed = self.is_pressed;
     }
@@ -0,0 +1,217 @@
+use crate::fighting_game::FightingGame;
+use crate::stage::Bounds;
+
+// Where the view is looking. In auto mode it works like Melee's camera, aiming at the middle
+// of every fighter and zooming out as they spread apart, easing toward that a little each
+// frame and never showing anything past the stage's camera bounds. In free mode it only
+// moves when it's panned or zoomed by hand.
+//
+// The position is the point in the middle of the screen and the view height is how many
+// stage units fit from the top of the screen to the bottom, so the same camera shows the
+// same thing at any resolution.
+pub struct Camera {
+    pub mode: CameraMode,
+    pub x: f64,
+    pub y: f64,
+    pub view_height: f64,
+    pub min_view_height: f64,
+    pub max_view_height: f64,
+    pub min_auto_view_height: f64,
+    pub smoothing: f64,
+    pub padding: f64,
+    pub has_framed_fighters: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CameraMode {
+    Auto,
+    Free,
+}
+
+impl Camera {
+    pub fn default() -> Self {
+        Self {
+            mode: CameraMode::Auto,
+            x: 0.0,
+            y: 20.0,
+            view_height: 150.0,
+            min_view_height: 10.0,
+            max_view_height: 600.0,
+            min_auto_view_height: 120.0,
+            smoothing: 0.1,
+            padding: 20.0,
+            has_framed_fighters: false,
+        }
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            CameraMode::Auto => CameraMode::Free,
+            CameraMode::Free => CameraMode::Auto,
+        };
+        self.has_framed_fighters = false;
+    }
+
+    // Zooming in makes the view height smaller.
+    pub fn zoom(&mut self, amount: f64) {
+        self.view_height *= 0.9_f64.powf(amount);
+        self.view_height = self.view_height.max(self.min_view_height).min(self.max_view_height);
+    }
+
+    // Should be called once per game update with the screen's width over its height. The
+    // first update in auto mode jumps straight to the fighters rather than easing in from
+    // wherever the camera was.
+    pub fn update(&mut self, game: &FightingGame, aspect_ratio: f64) {
+        if self.mode == CameraMode::Free {
+            return;
+        }
+        let (target_x, target_y, target_view_height) = self.find_target(game, aspect_ratio);
+        let smoothing = if self.has_framed_fighters { self.smoothing } else { 1.0 };
+        self.x += (target_x - self.x) * smoothing;
+        self.y += (target_y - self.y) * smoothing;
+        self.view_height += (target_view_height - self.view_height) * smoothing;
+        self.has_framed_fighters = true;
+    }
+
+    // Fits a padded box around the fighters in the view, then keeps the view inside the
+    // camera bounds.
+    fn find_target(&self, game: &FightingGame, aspect_ratio: f64) -> (f64, f64, f64) {
+        let camera_bounds = &game.stage.camera_bounds;
+        let fighter_bounds = game.players.iter()
+            .filter(|player| player.stocks > 0)
+            .map(|player| {
+                let fighter = &player.fighter;
+                Bounds {
+                    left: fighter.position.x + fighter.ecb.left.x,
+                    right: fighter.position.x + fighter.ecb.right.x,
+                    bottom: fighter.position.y + fighter.ecb.bottom.y,
+                    top: fighter.position.y + fighter.ecb.top.y,
+                }
+            })
+            .fold(None, |possible_bounds: Option<Bounds>, bounds| match possible_bounds {
+                Some(all_bounds) => Some(Bounds {
+                    left: all_bounds.left.min(bounds.left),
+                    right: all_bounds.right.max(bounds.right),
+                    bottom: all_bounds.bottom.min(bounds.bottom),
+                    top: all_bounds.top.max(bounds.top),
+                }),
+                None => Some(bounds),
+            });
+
+        let (center_x, center_y, width, height) = match fighter_bounds {
+            Some(bounds) => {
+                // Fighters past the camera bounds are followed only as far as the bounds go.
+                let left = bounds.left.max(camera_bounds.left).min(camera_bounds.right);
+                let right = bounds.right.min(camera_bounds.right).max(camera_bounds.left);
+                let bottom = bounds.bottom.max(camera_bounds.bottom).min(camera_bounds.top);
+                let top = bounds.top.min(camera_bounds.top).max(camera_bounds.bottom);
+                (
+                    0.5 * (left + right),
+                    0.5 * (bottom + top),
+                    right - left + 2.0 * self.padding,
+                    (top - bottom + 2.0 * self.padding).max(self.min_auto_view_height),
+                )
+            },
+            None => (
+                0.5 * (camera_bounds.left + camera_bounds.right),
+                0.5 * (camera_bounds.bottom + camera_bounds.top),
+                camera_bounds.right - camera_bounds.left,
+                camera_bounds.top - camera_bounds.bottom,
+            ),
+        };
+
+        // Zoomed out enough to fit the fighters, but not so far that the view is bigger than
+        // the camera bounds.
+        let fit_view_height = height.max(width / aspect_ratio);
+        let bounds_view_height = (camera_bounds.top - camera_bounds.bottom)
+            .min((camera_bounds.right - camera_bounds.left) / aspect_ratio);
+        let view_height = fit_view_height.min(bounds_view_height).min(self.max_view_height);
+
+        let half_width = 0.5 * view_height * aspect_ratio;
+        let half_height = 0.5 * view_height;
+        let x = clamp_or_middle(center_x, camera_bounds.left + half_width, camera_bounds.right - half_width);
+        let y = clamp_or_middle(center_y, camera_bounds.bottom + half_height, camera_bounds.top - half_height);
+        (x, y, view_height)
+    }
+}
+
+// If the range is empty the view is bigger than the bounds, so it stays centered on them.
+fn clamp_or_middle(value: f64, min: f64, max: f64) -> f64 {
+    if min > max {
+        0.5 * (min + max)
+    }
+    else {
+        value.max(min).min(max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::fighter::Fighter;
+    use crate::fighting_game::MatchState;
+    use crate::match_rules::MatchRules;
+    use crate::point_math::Point;
+    use crate::stage::Stage;
+
+    const ASPECT_RATIO: f64 = 4.0 / 3.0;
+    const TOLERANCE: f64 = 1e-9;
+
+    // The part of the stage the camera shows.
+    fn view(camera: &Camera) -> Bounds {
+        let half_width = 0.5 * camera.view_height * ASPECT_RATIO;
+        let half_height = 0.5 * camera.view_height;
+        Bounds {
+            left: camera.x - half_width,
+            right: camera.x + half_width,
+            bottom: camera.y - half_height,
+            top: camera.y + half_height,
+        }
+    }
+
+    fn is_inside(outer: &Bounds, inner: &Bounds) -> bool {
+        inner.left >= outer.left - TOLERANCE && inner.right <= outer.right + TOLERANCE
+        && inner.bottom >= outer.bottom - TOLERANCE && inner.top <= outer.top + TOLERANCE
+    }
+
+    // Two Foxes on Final Destination, moved around between camera updates: side by side,
+    // spread to opposite corners, and one past the camera bounds. The view never leaves the
+    // bounds while it eases, and once settled it shows every Fox that is inside them.
+    #[test]
+    fn auto_camera_frames_both_players_inside_the_bounds() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join("stages").join("final_destination.json");
+        let mut game = FightingGame::new(MatchRules::default(), Stage::from_file(&path).unwrap(), vec![Fighter::fox(), Fighter::fox()]);
+        game.change_match_state(MatchState::Playing);
+        let mut camera = Camera::default();
+
+        let placements = [
+            [Point { x: -20.0, y: 0.0 }, Point { x: 20.0, y: 0.0 }],
+            [Point { x: -100.0, y: 0.0 }, Point { x: 100.0, y: 80.0 }],
+            [Point { x: -190.0, y: 0.0 }, Point { x: 0.0, y: 0.0 }],
+        ];
+        for positions in placements {
+            for (player, position) in game.players.iter_mut().zip(&positions) {
+                player.fighter.position = position.clone();
+            }
+            for _ in 0..300 {
+                camera.update(&game, ASPECT_RATIO);
+                assert!(is_inside(&game.stage.camera_bounds, &view(&camera)), "the view left the camera bounds with Foxes at ({}, {}) and ({}, {})", positions[0].x, positions[0].y, positions[1].x, positions[1].y);
+            }
+
+            for player in &game.players {
+                let fighter = &player.fighter;
+                let fighter_bounds = Bounds {
+                    left: fighter.position.x + fighter.ecb.left.x,
+                    right: fighter.position.x + fighter.ecb.right.x,
+                    bottom: fighter.position.y + fighter.ecb.bottom.y,
+                    top: fighter.position.y + fighter.ecb.top.y,
+                };
+                if is_inside(&game.stage.camera_bounds, &fighter_bounds) {
+                    assert!(is_inside(&view(&camera), &fighter_bounds), "Fox at ({}, {}) is out of view", fighter.position.x, fighter.position.y);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+// Everything the renderer draws goes through a canvas, so the same drawing code can go to
+// the window or to an image without one. Positions are in pixels from the top left and
+// colors are RGBA from 0 to 1, the same as piston uses.
+pub trait Canvas {
+    fn clear(&mut self, color: [f32; 4]);
+
+    // The line is [x1, y1, x2, y2] and the radius is half its thickness.
+    fn draw_line(&mut self, color: [f32; 4], radius: f64, line: [f64; 4]);
+
+    fn draw_polygon(&mut self, color: [f32; 4], points: &[[f64; 2]]);
+
+    // The rectangle is [x, y, width, height].
+    fn draw_rectangle(&mut self, color: [f32; 4], rectangle: [f64; 4]);
+
+    fn draw_circle(&mut self, color: [f32; 4], center: [f64; 2], radius: f64, is_filled: bool);
+
+    // The y position is the baseline of the text.
+    fn draw_text(&mut self, text: &str, size: u32, color: [f32; 4], x: f64, y: f64);
+}
+
+// Fits a virtual screen into the window as big as it goes without stretching, centered
+// with bars on whichever sides are left over.
+#[derive(Copy, Clone)]
+pub struct Viewport {
+    pub x: f64,
+    pub y: f64,
+    pub scale: f64,
+    pub virtual_width: f64,
+    pub virtual_height: f64,
+    pub window_width: f64,
+    pub window_height: f64,
+}
+
+impl Viewport {
+    pub fn fit(virtual_width: f64, virtual_height: f64, window_width: f64, window_height: f64) -> Self {
+        let scale = (window_width / virtual_width).min(window_height / virtual_height);
+        Self {
+            x: 0.5 * (window_width - scale * virtual_width),
+            y: 0.5 * (window_height - scale * virtual_height),
+            scale,
+            virtual_width,
+            virtual_height,
+            window_width,
+            window_height,
+        }
+    }
+
+    pub fn window_position(&self, point: [f64; 2]) -> [f64; 2] {
+        [self.x + self.scale * point[0], self.y + self.scale * point[1]]
+    }
+
+    // Covers anything drawn past the edges of the virtual screen.
+    pub fn draw_bars(&self, canvas: &mut impl Canvas, color: [f32; 4]) {
+        let right = self.x + self.scale * self.virtual_width;
+        let bottom = self.y + self.scale * self.virtual_height;
+        canvas.draw_rectangle(color, [0.0, 0.0, self.x, self.window_height]);
+        canvas.draw_rectangle(color, [right, 0.0, self.window_width - right, self.window_height]);
+        canvas.draw_rectangle(color, [0.0, 0.0, self.window_width, self.y]);
+        canvas.draw_rectangle(color, [0.0, bottom, self.window_width, self.window_height - bottom]);
+    }
+}
+
+// Takes positions and sizes on the virtual screen and draws them where they end up in
+// the window.
+pub struct ViewportCanvas<'a, C: Canvas> {
+    pub canvas: &'a mut C,
+    pub viewport: Viewport,
+}
+
+impl<'a, C: Canvas> Canvas for ViewportCanvas<'a, C> {
+    fn clear(&mut self, color: [f32; 4]) {
+        self.canvas.clear(color);
+    }
+
+    fn draw_line(&mut self, color: [f32; 4], radius: f64, line: [f64; 4]) {
+        let start = self.viewport.window_position([line[0], line[1]]);
+        let end = self.viewport.window_position([line[2], line[3]]);
+        self.canvas.draw_line(color, self.viewport.scale * radius, [start[0], start[1], end[0], end[1]]);
+    }
+
+    fn draw_polygon(&mut self, color: [f32; 4], points: &[[f64; 2]]) {
+        let window_points: Vec<[f64; 2]> = points.iter().map(|point| self.viewport.window_position(*point)).collect();
+        self.canvas.draw_polygon(color, &window_points);
+    }
+
+    fn draw_rectangle(&mut self, color: [f32; 4], rectangle: [f64; 4]) {
+        let corner = self.viewport.window_position([rectangle[0], rectangle[1]]);
+        let scale = self.viewport.scale;
+        self.canvas.draw_rectangle(color, [corner[0], corner[1], scale * rectangle[2], scale * rectangle[3]]);
+    }
+
+    fn draw_circle(&mut self, color: [f32; 4], center: [f64; 2], radius: f64, is_filled: bool) {
+        self.canvas.draw_circle(color, self.viewport.window_position(center), self.viewport.scale * radius, is_filled);
+    }
+
+    fn draw_text(&mut self, text: &str, size: u32, color: [f32; 4], x: f64, y: f64) {
+        let window_size = (self.viewport.scale * size as f64).round().max(1.0) as u32;
+        let position = self.viewport.window_position([x, y]);
+        self.canvas.draw_text(text, window_size, color, position[0], position[1]);
+    }
+}
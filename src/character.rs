@@ -0,0 +1,335 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::point_math::Point;
+use crate::fighter::{Fighter, FighterAttributes, FighterState};
+use crate::ecb::{ECB, ECBKeyframe};
+use crate::attack::{Attack, AttackKind, Hitbox};
+
+pub enum CharacterError {
+    Io(String, std::io::Error),
+    Parse(String, String, serde_json::Error),
+    Invalid(String, Vec<String>),
+}
+
+impl fmt::Display for CharacterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CharacterError::Io(source, error) => write!(f, "Could not read character {}: {}", source, error),
+            CharacterError::Parse(source, field, error) => write!(f, "Could not parse character {} at {}: {}", source, field, error),
+            CharacterError::Invalid(source, problems) => {
+                write!(f, "Character {} is invalid:", source)?;
+                for problem in problems {
+                    write!(f, "\n    {}", problem)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl fmt::Debug for CharacterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Fighter {
+    pub fn from_file(path: &Path) -> Result<Self, CharacterError> {
+        let source = path.display().to_string();
+        let text = fs::read_to_string(path).map_err(|error| CharacterError::Io(source.clone(), error))?;
+        Self::from_json(&text, &source)
+    }
+
+    // The source is only used to say where errors came from.
+    pub fn from_json(text: &str, source: &str) -> Result<Self, CharacterError> {
+        let deserializer = &mut serde_json::Deserializer::from_str(text);
+        let character_file: CharacterFile = serde_path_to_error::deserialize(deserializer)
+            .map_err(|error| CharacterError::Parse(
+                source.to_string(),
+                error.path().to_string(),
+                error.into_inner(),
+            ))?;
+
+        let problems = character_file.validate();
+        if !problems.is_empty() {
+            return Err(CharacterError::Invalid(source.to_string(), problems));
+        }
+
+        Ok(character_file.into_fighter())
+    }
+}
+
+// The stock characters are built in for the tests, which play every one of them.
+#[cfg(test)]
+impl Fighter {
+    pub fn fox() -> Self {
+        Self::from_json(include_str!("../assets/characters/fox.json"), "fox.json").unwrap()
+    }
+
+    pub fn falco() -> Self {
+        Self::from_json(include_str!("../assets/characters/falco.json"), "falco.json").unwrap()
+    }
+
+    pub fn marth() -> Self {
+        Self::from_json(include_str!("../assets/characters/marth.json"), "marth.json").unwrap()
+    }
+
+    pub fn sheik() -> Self {
+        Self::from_json(include_str!("../assets/characters/sheik.json"), "sheik.json").unwrap()
+    }
+
+    pub fn peach() -> Self {
+        Self::from_json(include_str!("../assets/characters/peach.json"), "peach.json").unwrap()
+    }
+
+    pub fn jigglypuff() -> Self {
+        Self::from_json(include_str!("../assets/characters/jigglypuff.json"), "jigglypuff.json").unwrap()
+    }
+
+    pub fn captain_falcon() -> Self {
+        Self::from_json(include_str!("../assets/characters/captain_falcon.json"), "captain_falcon.json").unwrap()
+    }
+}
+
+// Everything below mirrors the layout of the character files.
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CharacterFile {
+    name: String,
+    attributes: FighterAttributes,
+    ecb: ECBDataFile,
+    attacks: Vec<AttackFile>,
+}
+
+// ECBs are symmetrical diamonds, see ECB::diamond.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DiamondFile {
+    bottom: f64,
+    top: f64,
+    half_width: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ECBDataFile {
+    ground: DiamondFile,
+    air: DiamondFile,
+    air_transition_frames: u32,
+    keyframes: Vec<ECBKeyframeFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ECBKeyframeFile {
+    state: FighterState,
+    frame: u32,
+    ecb: DiamondFile,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AttackFile {
+    name: String,
+    kind: AttackKind,
+    total_frames: u32,
+    landing_lag: u32,
+    l_cancel_landing_lag: u32,
+    hitboxes: Vec<HitboxFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HitboxFile {
+    start_frame: u32,
+    end_frame: u32,
+    x: f64,
+    y: f64,
+    radius: f64,
+    damage: f64,
+    angle: f64,
+    base_knockback: f64,
+    knockback_growth: f64,
+}
+
+impl DiamondFile {
+    fn to_ecb(&self) -> ECB {
+        ECB::diamond(self.bottom, self.top, self.half_width)
+    }
+
+    fn validate(&self, field: &str, problems: &mut Vec<String>) {
+        if self.top <= self.bottom {
+            problems.push(format!("{}.top has to be above {}.bottom.", field, field));
+        }
+        if self.half_width <= 0.0 {
+            problems.push(format!("{}.half_width has to be more than 0.", field));
+        }
+    }
+}
+
+impl CharacterFile {
+    // Collects every problem instead of stopping at the first one. Each problem
+    // names the field it is about.
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        let attributes = &self.attributes;
+        let positive_attributes = [
+            ("weight", attributes.weight),
+            ("dash_max_velocity", attributes.dash_max_velocity),
+            ("walk_max_velocity", attributes.walk_max_velocity),
+            ("air_max_velocity", attributes.air_max_velocity),
+            ("full_hop_velocity", attributes.full_hop_velocity),
+            ("short_hop_velocity", attributes.short_hop_velocity),
+            ("fall_velocity", attributes.fall_velocity),
+            ("fast_fall_velocity", attributes.fast_fall_velocity),
+            ("gravity", attributes.gravity),
+        ];
+        for (name, value) in &positive_attributes {
+            if *value <= 0.0 {
+                problems.push(format!("attributes.{} has to be more than 0.", name));
+            }
+        }
+
+        let non_negative_attributes = [
+            ("ground_friction", attributes.ground_friction),
+            ("dash_start_velocity", attributes.dash_start_velocity),
+            ("dash_base_acceleration", attributes.dash_base_acceleration),
+            ("dash_axis_acceleration", attributes.dash_axis_acceleration),
+            ("walk_start_velocity", attributes.walk_start_velocity),
+            ("walk_acceleration", attributes.walk_acceleration),
+            ("air_friction", attributes.air_friction),
+            ("air_base_acceleration", attributes.air_base_acceleration),
+            ("air_axis_acceleration", attributes.air_axis_acceleration),
+            ("jump_velocity_dampening", attributes.jump_velocity_dampening),
+            ("jump_max_horizontal_velocity", attributes.jump_max_horizontal_velocity),
+            ("jump_start_horizontal_velocity", attributes.jump_start_horizontal_velocity),
+            ("air_jump_velocity_multiplier", attributes.air_jump_velocity_multiplier),
+            ("air_jump_horizontal_axis_multiplier", attributes.air_jump_horizontal_axis_multiplier),
+        ];
+        for (name, value) in &non_negative_attributes {
+            if *value < 0.0 {
+                problems.push(format!("attributes.{} can't be negative.", name));
+            }
+        }
+
+        if attributes.jump_squat_frames == 0 {
+            problems.push("attributes.jump_squat_frames has to be at least 1.".to_string());
+        }
+        if attributes.short_hop_velocity > attributes.full_hop_velocity {
+            problems.push("attributes.short_hop_velocity can't be more than attributes.full_hop_velocity.".to_string());
+        }
+        if attributes.fast_fall_velocity < attributes.fall_velocity {
+            problems.push("attributes.fast_fall_velocity can't be less than attributes.fall_velocity.".to_string());
+        }
+        if attributes.air_jump_velocity_decay <= 0.0 || attributes.air_jump_velocity_decay > 1.0 {
+            problems.push("attributes.air_jump_velocity_decay has to be more than 0 and at most 1.".to_string());
+        }
+        if attributes.dash_min_frames > attributes.dash_max_frames {
+            problems.push("attributes.dash_min_frames can't be more than attributes.dash_max_frames.".to_string());
+        }
+
+        self.ecb.ground.validate("ecb.ground", &mut problems);
+        self.ecb.air.validate("ecb.air", &mut problems);
+        if self.ecb.air_transition_frames == 0 {
+            problems.push("ecb.air_transition_frames has to be at least 1.".to_string());
+        }
+        for (keyframe_index, keyframe) in self.ecb.keyframes.iter().enumerate() {
+            keyframe.ecb.validate(&format!("ecb.keyframes[{}].ecb", keyframe_index), &mut problems);
+
+            // Keyframes of the same state have to be in frame order.
+            let is_out_of_order = self.ecb.keyframes[..keyframe_index].iter()
+                .any(|earlier| earlier.state == keyframe.state && earlier.frame >= keyframe.frame);
+            if is_out_of_order {
+                problems.push(format!(
+                    "ecb.keyframes[{}].frame has to come after the earlier keyframes of the same state.",
+                    keyframe_index,
+                ));
+            }
+        }
+
+        for (attack_index, attack) in self.attacks.iter().enumerate() {
+            if attack.total_frames == 0 {
+                problems.push(format!("attacks[{}].total_frames has to be at least 1.", attack_index));
+            }
+            if attack.kind == AttackKind::Ground && attack.landing_lag > 0 {
+                problems.push(format!("attacks[{}].landing_lag has to be 0 for a ground attack.", attack_index));
+            }
+            if attack.l_cancel_landing_lag > attack.landing_lag {
+                problems.push(format!(
+                    "attacks[{}].l_cancel_landing_lag can't be more than attacks[{}].landing_lag.",
+                    attack_index,
+                    attack_index,
+                ));
+            }
+            for (hitbox_index, hitbox) in attack.hitboxes.iter().enumerate() {
+                let field = format!("attacks[{}].hitboxes[{}]", attack_index, hitbox_index);
+                if hitbox.start_frame > hitbox.end_frame {
+                    problems.push(format!("{}.start_frame can't be after {}.end_frame.", field, field));
+                }
+                if hitbox.end_frame >= attack.total_frames {
+                    problems.push(format!("{}.end_frame has to be before attacks[{}].total_frames.", field, attack_index));
+                }
+                if hitbox.radius <= 0.0 {
+                    problems.push(format!("{}.radius has to be more than 0.", field));
+                }
+                if hitbox.damage < 0.0 {
+                    problems.push(format!("{}.damage can't be negative.", field));
+                }
+            }
+        }
+
+        problems
+    }
+
+    fn into_fighter(self) -> Fighter {
+        Fighter {
+            name: self.name,
+            air_jumps_left: self.attributes.air_jumps,
+            float_frames_left: self.attributes.float_frames,
+            attributes: self.attributes,
+            ecb: self.ecb.ground.to_ecb(),
+            previous_ecb: self.ecb.ground.to_ecb(),
+            ground_ecb: self.ecb.ground.to_ecb(),
+            air_ecb: self.ecb.air.to_ecb(),
+            ecb_air_transition_frames: self.ecb.air_transition_frames,
+            ecb_keyframes: self.ecb.keyframes.iter()
+                .map(|keyframe| ECBKeyframe {
+                    state: keyframe.state,
+                    frame: keyframe.frame,
+                    ecb: keyframe.ecb.to_ecb(),
+                })
+                .collect(),
+            attacks: self.attacks.iter()
+                .map(|attack| Attack {
+                    name: attack.name.clone(),
+                    kind: attack.kind,
+                    total_frames: attack.total_frames,
+                    landing_lag: attack.landing_lag,
+                    l_cancel_landing_lag: attack.l_cancel_landing_lag,
+                    hitboxes: attack.hitboxes.iter()
+                        .map(|hitbox| Hitbox {
+                            start_frame: hitbox.start_frame,
+                            end_frame: hitbox.end_frame,
+                            offset: Point {
+                                x: hitbox.x,
+                                y: hitbox.y,
+                            },
+                            radius: hitbox.radius,
+                            damage: hitbox.damage,
+                            angle: hitbox.angle,
+                            base_knockback: hitbox.base_knockback,
+                            knockback_growth: hitbox.knockback_growth,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            ..Fighter::default()
+        }
+    }
+}
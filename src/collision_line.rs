@@ -0,0 +1,107 @@
+use crate::vector_math::Vector;
+use crate::line_math::LineSegment;
+
+#[derive(Clone)]
+pub struct CollisionLine {
+    pub segment: LineSegment,
+    pub is_pass_through: bool,
+}
+
+impl CollisionLine {
+    pub fn solid(segment: LineSegment) -> Self {
+        Self {
+            segment,
+            is_pass_through: false,
+        }
+    }
+
+    // Pass through lines are soft platforms that can be landed on from above,
+    // jumped up through, and fallen through.
+    pub fn pass_through(segment: LineSegment) -> Self {
+        Self {
+            segment,
+            is_pass_through: true,
+        }
+    }
+
+    // Lines are classified by which way their normal faces. Anything
+    // steeper than 60 degrees is considered a wall.
+    pub fn is_ground(&self) -> bool {
+        self.segment.normal().y >= 0.5
+    }
+
+    pub fn is_ceiling(&self) -> bool {
+        self.segment.normal().y <= -0.5
+    }
+}
+
+// Moving poly lines follow their path by offsetting every line. The movement is
+// how far they moved during the last frame, which collisions need to account for.
+#[derive(Clone)]
+pub struct CollisionPolyLine {
+    pub lines: Vec<CollisionLine>,
+    pub path: Option<CollisionPath>,
+    pub offset: Vector,
+    pub movement: Vector,
+}
+
+// A looping path that goes from each waypoint to the next over the number of frames
+// given for that waypoint. Repeating a waypoint makes the path wait there.
+#[derive(Clone)]
+pub struct CollisionPath {
+    pub waypoints: Vec<Vector>,
+    pub frames: Vec<u32>,
+}
+
+impl CollisionPath {
+    pub fn offset_at_frame(&self, frame: u32) -> Vector {
+        let loop_frames: u32 = self.frames.iter().sum();
+        let mut frame_in_loop = if loop_frames > 0 { frame % loop_frames } else { 0 };
+
+        for (waypoint_index, waypoint_frames) in self.frames.iter().enumerate() {
+            if frame_in_loop < *waypoint_frames {
+                let from = &self.waypoints[waypoint_index];
+                let to = &self.waypoints[(waypoint_index + 1) % self.waypoints.len()];
+                let interpolation = frame_in_loop as f64 / *waypoint_frames as f64;
+                return Vector {
+                    x: from.x + interpolation * (to.x - from.x),
+                    y: from.y + interpolation * (to.y - from.y),
+                };
+            }
+            frame_in_loop -= waypoint_frames;
+        }
+
+        self.waypoints.first().cloned().unwrap_or_else(Vector::default)
+    }
+}
+
+impl CollisionPolyLine {
+    pub fn from_lines(lines: Vec<CollisionLine>) -> Self {
+        Self {
+            lines,
+            path: None,
+            offset: Vector::default(),
+            movement: Vector::default(),
+        }
+    }
+
+    pub fn move_to_frame(&mut self, frame: u32) {
+        let new_offset = match &self.path {
+            Some(path) => path.offset_at_frame(frame),
+            None => return,
+        };
+
+        self.movement = Vector {
+            x: new_offset.x - self.offset.x,
+            y: new_offset.y - self.offset.y,
+        };
+        self.offset = new_offset;
+
+        for collision_line in &mut self.lines {
+            for point in &mut [&mut collision_line.segment.point_a, &mut collision_line.segment.point_b] {
+                point.x += self.movement.x;
+                point.y += self.movement.y;
+            }
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use crate::analog_axis::{self, AnalogAxis};
 use crate::button::Button;
 
+#[derive(Clone)]
 pub struct ControllerState {
     pub x_axis: AnalogAxis,
     pub y_axis: AnalogAxis,
@@ -57,45 +57,23 @@ impl DigitalInput {
 
     pub fn update_states_with_piston_window_event(&mut self, event: &Event) {
         if let Some(args) = event.button_args() {
-            match args.state {
-                ButtonState::Press => match args.button {
-                    Button::Keyboard(key) => match key {
-                        Key::A => self.left = true,
-                        Key::D => self.right = true,
-                        Key::S => self.down = true,
-                        Key::W => self.up = true,
-                        Key::Backslash => self.x = true,
-                        Key::LeftBracket => self.y = true,
-                        Key::Equals => self.z = true,
-                        Key::Semicolon => self.l = true,
-                        Key::RightBracket => self.r = true,
-                        Key::D5 => self.start = true,
-                        Key::V => self.d_left = true,
-                        Key::N => self.d_right = true,
-                        Key::B => self.d_down = true,
-                        Key::G => self.d_up = true,
-                        _ => ()
-                    },
-                    _ => ()
-                },
-                ButtonState::Release => match args.button {
-                    Button::Keyboard(key) => match key {
-                        Key::A => self.left = false,
-                        Key::D => self.right = false,
-                        Key::S => self.down = false,
-                        Key::W => self.up = false,
-                        Key::Backslash => self.x = false,
-                        Key::LeftBracket => self.y = false,
-                        Key::Equals => self.z = false,
-                        Key::Semicolon => self.l = false,
-                        Key::RightBracket => self.r = false,
-                        Key::D5 => self.start = false,
-                        Key::V => self.d_left = false,
-                        Key::N => self.d_right = false,
-                        Key::B => self.d_down = false,
-                        Key::G => self.d_up = false,
-                        _ => ()
-                    },
+            if let Button::Keyboard(key) = args.button {
+                let is_pressed = args.state == ButtonState::Press;
+                match key {
+                    Key::A => self.left = is_pressed,
+                    Key::D => self.right = is_pressed,
+                    Key::S => self.down = is_pressed,
+                    Key::W => self.up = is_pressed,
+                    Key::Backslash => self.x = is_pressed,
+                    Key::LeftBracket => self.y = is_pressed,
+                    Key::Equals => self.z = is_pressed,
+                    Key::Semicolon => self.l = is_pressed,
+                    Key::RightBracket => self.r = is_pressed,
+                    Key::D5 => self.start = is_pressed,
+                    Key::V => self.d_left = is_pressed,
+                    Key::N => self.d_right = is_pressed,
+                    Key::B => self.d_down = is_pressed,
+                    Key::G => self.d_up = is_pressed,
                     _ => ()
                 }
             }
@@ -1,8 +1,43 @@
 use crate::point_math::Point;
+use crate::fighter::FighterState;
 
+// Environmental collision box, named the way Melee names it.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone)]
 pub struct ECB {
     pub bottom: Point,
     pub left: Point,
     pub top: Point,
     pub right: Point,
 }
+
+impl ECB {
+    // Builds a symmetrical diamond. The sides are placed halfway between the top and bottom.
+    pub fn diamond(bottom: f64, top: f64, half_width: f64) -> Self {
+        let middle = 0.5 * (bottom + top);
+        Self {
+            bottom: Point { x: 0.0, y: bottom },
+            left: Point { x: -half_width, y: middle },
+            top: Point { x: 0.0, y: top },
+            right: Point { x: half_width, y: middle },
+        }
+    }
+
+    pub fn lerp(&self, other_ecb: &Self, interpolation: f64) -> Self {
+        Self {
+            bottom: self.bottom.lerp(&other_ecb.bottom, interpolation),
+            left: self.left.lerp(&other_ecb.left, interpolation),
+            top: self.top.lerp(&other_ecb.top, interpolation),
+            right: self.right.lerp(&other_ecb.right, interpolation),
+        }
+    }
+}
+
+// An ECB that a fighter takes on from a given frame of a state until the next keyframe
+// of that state. Keyframes of the same state are expected to be in frame order.
+#[derive(Clone)]
+pub struct ECBKeyframe {
+    pub state: FighterState,
+    pub frame: u32,
+    pub ecb: ECB,
+}
@@ -1,18 +1,40 @@
-use std::f64::consts::PI;
-
 use crate::controller_state::ControllerState;
 use crate::analog_axis::AnalogAxis;
 use crate::point_math::Point;
 use crate::vector_math::Vector;
-use crate::ecb::ECB;
+use crate::ecb::{ECB, ECBKeyframe};
+use crate::attack::{Attack, AttackKind, Hitbox, Hurtbox};
+use crate::knockback;
+
+use serde::{Deserialize, Serialize};
+
+// How many frames before landing an aerial pressing L, R or Z still counts.
+pub const L_CANCEL_WINDOW_FRAMES: u32 = 7;
 
+// Pressing L or R up to 20 frames before landing in hitstun techs. A tech in place
+// lasts 26 frames and the first 20 of them are invincible.
+pub const TECH_WINDOW_FRAMES: u32 = 20;
+pub const TECH_FRAMES: u32 = 26;
+pub const TECH_INVINCIBLE_FRAMES: u32 = 20;
+
+// There are no get up options yet, so missing a tech keeps the fighter down for a
+// while and then they stand up on their own.
+pub const KNOCKED_DOWN_FRAMES: u32 = 30;
+
+#[derive(Clone)]
 pub struct Fighter {
+    pub name: String,
     pub input: ControllerState,
     pub position: Point,
     pub previous_position: Point,
     pub velocity: Vector,
     pub ground_angle: f64,
+    pub is_on_platform: bool,
+    pub is_near_ledge: bool,
     pub air_jumps_left: u32,
+    pub air_jump_rise_frames_left: u32,
+    pub air_jump_rise_velocity: f64,
+    pub float_frames_left: u32,
     pub is_facing_right: bool,
     pub was_facing_right: bool,
     pub state: FighterState,
@@ -23,7 +45,37 @@ pub struct Fighter {
     pub run_turn_was_facing_right_initially: bool,
     pub run_turn_has_changed_direction: bool,
     pub run_turn_has_fully_turned: bool,
+    pub air_frames: u32,
+    pub ecb: ECB,
+    pub previous_ecb: ECB,
+    pub invincibility_frames: u32,
+    pub current_attack: Option<usize>,
+    pub landing_lag_frames: u32,
+    pub l_cancel_frames_left: u32,
+    pub last_landing_was_l_cancelled: Option<bool>,
+    pub hit_targets: Vec<usize>,
+    pub percent: f64,
+    pub knockback_velocity: Vector,
+    pub hitstun_frames: u32,
+    pub shieldstun_frames: u32,
+    pub tech_frames_left: u32,
+
+    pub attributes: FighterAttributes,
+    pub ground_ecb: ECB,
+    pub air_ecb: ECB,
+    pub ecb_air_transition_frames: u32,
+    pub ecb_keyframes: Vec<ECBKeyframe>,
+    pub attacks: Vec<Attack>,
+    pub respawn_platform_frames: u32,
+    pub respawn_invincibility_frames: u32,
+}
 
+// The numbers that make one character move differently from another. These are loaded
+// straight from the attributes in a character file.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FighterAttributes {
+    pub weight: f64,
     pub ground_friction: f64,
     pub dash_start_velocity: f64,
     pub dash_max_velocity: f64,
@@ -47,36 +99,22 @@ pub struct Fighter {
     pub air_jump_velocity_multiplier: f64,
     pub air_jump_horizontal_axis_multiplier: f64,
     pub air_jumps: u32,
+    pub air_jump_velocity_decay: f64,
+    pub air_jump_rise_frames: u32,
+    pub float_frames: u32,
     pub gravity: f64,
     pub dash_min_frames: u32,
     pub dash_max_frames: u32,
     pub slow_dash_back_frames: u32,
     pub turn_frames: u32,
     pub run_brake_frames: u32,
-    pub ecb: ECB,
+    pub land_special_frames: u32,
 }
 
-// Character builders.
-impl Fighter {
+impl FighterAttributes {
     pub fn default() -> Self {
         Self {
-            input: ControllerState::default(),
-            position: Point::default(),
-            previous_position: Point::default(),
-            velocity: Vector::default(),
-            ground_angle: 0.0,
-            air_jumps_left: 1,
-            is_facing_right: true,
-            was_facing_right: true,
-            state: FighterState::Idle,
-            previous_state: FighterState::Idle,
-            state_frame: 0,
-            dash_should_reset_velocity: false,
-            run_turn_melee_frame: 0,
-            run_turn_was_facing_right_initially: true,
-            run_turn_has_changed_direction: false,
-            run_turn_has_fully_turned: false,
-
+            weight: 100.0,
             ground_friction: 0.0,
             dash_start_velocity: 0.0,
             dash_max_velocity: 0.0,
@@ -100,60 +138,89 @@ impl Fighter {
             air_jump_velocity_multiplier: 0.0,
             air_jump_horizontal_axis_multiplier: 0.0,
             air_jumps: 1,
+            air_jump_velocity_decay: 1.0,
+            air_jump_rise_frames: 0,
+            float_frames: 0,
             gravity: 0.0,
             dash_min_frames: 0,
             dash_max_frames: 0,
             slow_dash_back_frames: 0,
             turn_frames: 0,
             run_brake_frames: 0,
-            ecb: ECB {
-                bottom: Point { x: 0.0, y: 0.0 },
-                left: Point { x: -2.3, y: 6.0 },
-                top: Point { x: 0.0, y: 12.0 },
-                right: Point { x: 2.3, y: 6.0 },
-            }
+            land_special_frames: 0,
         }
     }
+}
 
-    pub fn fox() -> Self {
-        Self{
-            ground_friction: 0.08,
-            dash_start_velocity: 1.9,
-            dash_max_velocity: 2.2,
-            dash_base_acceleration: 0.02,
-            dash_axis_acceleration: 0.1,
-            walk_start_velocity: 0.16,
-            walk_max_velocity: 1.6,
-            walk_acceleration: 0.2,
-            air_friction: 0.02,
-            air_base_acceleration: 0.02,
-            air_axis_acceleration: 0.06,
-            air_max_velocity: 0.83,
-            jump_squat_frames: 3,
-            jump_velocity_dampening: 0.83,
-            jump_max_horizontal_velocity: 1.7,
-            jump_start_horizontal_velocity: 0.72,
-            short_hop_velocity: 2.1,
-            full_hop_velocity: 3.68,
-            fall_velocity: 2.8,
-            fast_fall_velocity: 3.4,
-            air_jump_velocity_multiplier: 1.2,
-            air_jump_horizontal_axis_multiplier: 0.9,
-            air_jumps: 1,
-            gravity: 0.23,
-            dash_min_frames: 11,
-            dash_max_frames: 21,
-            slow_dash_back_frames: 5,
-            turn_frames: 11,
-            run_brake_frames: 18,
-
-            ..Fighter::default()
+// Character builders.
+impl Fighter {
+    pub fn default() -> Self {
+        Self {
+            name: String::new(),
+            input: ControllerState::default(),
+            position: Point::default(),
+            previous_position: Point::default(),
+            velocity: Vector::default(),
+            ground_angle: 0.0,
+            is_on_platform: false,
+            is_near_ledge: false,
+            air_jumps_left: 1,
+            air_jump_rise_frames_left: 0,
+            air_jump_rise_velocity: 0.0,
+            float_frames_left: 0,
+            is_facing_right: true,
+            was_facing_right: true,
+            state: FighterState::Idle,
+            previous_state: FighterState::Idle,
+            state_frame: 0,
+            dash_should_reset_velocity: false,
+            run_turn_melee_frame: 0,
+            run_turn_was_facing_right_initially: true,
+            run_turn_has_changed_direction: false,
+            run_turn_has_fully_turned: false,
+            air_frames: 0,
+            ecb: ECB::diamond(0.0, 12.0, 2.3),
+            previous_ecb: ECB::diamond(0.0, 12.0, 2.3),
+            invincibility_frames: 0,
+            current_attack: None,
+            landing_lag_frames: 0,
+            l_cancel_frames_left: 0,
+            last_landing_was_l_cancelled: None,
+            hit_targets: Vec::new(),
+            percent: 0.0,
+            knockback_velocity: Vector::default(),
+            hitstun_frames: 0,
+            shieldstun_frames: 0,
+            tech_frames_left: 0,
+
+            attributes: FighterAttributes::default(),
+            ground_ecb: ECB::diamond(0.0, 12.0, 2.3),
+            air_ecb: ECB::diamond(0.0, 12.0, 2.3),
+            ecb_air_transition_frames: 1,
+            ecb_keyframes: Vec::new(),
+            attacks: Vec::new(),
+            respawn_platform_frames: 300,
+            respawn_invincibility_frames: 120,
         }
     }
 }
 
 // Methods.
 impl Fighter {
+    // Takes on the attributes, ECB data, and attacks of another character while keeping
+    // the current state, position, and velocity. Used when character data is reloaded.
+    pub fn apply_character(&mut self, character: &Self) {
+        self.name = character.name.clone();
+        self.attributes = character.attributes.clone();
+        self.air_jumps_left = self.air_jumps_left.min(character.attributes.air_jumps);
+        self.float_frames_left = self.float_frames_left.min(character.attributes.float_frames);
+        self.ground_ecb = character.ground_ecb.clone();
+        self.air_ecb = character.air_ecb.clone();
+        self.ecb_air_transition_frames = character.ecb_air_transition_frames;
+        self.ecb_keyframes = character.ecb_keyframes.clone();
+        self.attacks = character.attacks.clone();
+    }
+
     pub fn change_state(&mut self, new_state: FighterState) {
         self.state_frame = 0;
         self.previous_state = self.state;
@@ -169,8 +236,15 @@ impl Fighter {
         }
     }
 
-    pub fn just_turned(&self) -> bool {
-        self.is_facing_right != self.was_facing_right
+    pub fn ground_direction(&self) -> Vector {
+        Vector {
+            x: self.ground_angle.cos(),
+            y: self.ground_angle.sin(),
+        }
+    }
+
+    pub fn ground_speed(&self) -> f64 {
+        self.velocity.dot(&self.ground_direction())
     }
 
     pub fn state_as_string(&self) -> String {
@@ -187,23 +261,259 @@ impl Fighter {
             FighterState::AirDodge => String::from("AirDodge"),
             FighterState::Land => String::from("Land"),
             FighterState::LandSpecial => String::from("LandSpecial"),
+            FighterState::FallThrough => String::from("FallThrough"),
+            FighterState::Teeter => String::from("Teeter"),
+            FighterState::Respawn => String::from("Respawn"),
+            FighterState::Float => String::from("Float"),
+            FighterState::Attack => String::from("Attack"),
+            FighterState::AerialAttack => String::from("AerialAttack"),
+            FighterState::LandingLag => String::from("LandingLag"),
+            FighterState::Hitstun => String::from("Hitstun"),
+            FighterState::Crouch => String::from("Crouch"),
+            FighterState::Shield => String::from("Shield"),
+            FighterState::ShieldStun => String::from("ShieldStun"),
+            FighterState::Tech => String::from("Tech"),
+            FighterState::KnockedDown => String::from("KnockedDown"),
+        }
+    }
+
+    // How many frames the current state lasts if it ends on its own.
+    pub fn state_total_frames(&self) -> Option<u32> {
+        match self.state {
+            FighterState::Turn => Some(self.attributes.turn_frames),
+            FighterState::Dash => Some(self.attributes.dash_max_frames),
+            FighterState::RunBrake => Some(self.attributes.run_brake_frames),
+            FighterState::JumpSquat => Some(self.attributes.jump_squat_frames),
+            FighterState::Land => Some(2),
+            FighterState::LandSpecial => Some(self.attributes.land_special_frames),
+            FighterState::Respawn => Some(self.respawn_platform_frames),
+            FighterState::Float => Some(self.attributes.float_frames),
+            FighterState::Attack => self.attack().map(|attack| attack.total_frames),
+            FighterState::AerialAttack => self.attack().map(|attack| attack.total_frames),
+            FighterState::LandingLag => Some(self.landing_lag_frames),
+            FighterState::Hitstun => Some(self.hitstun_frames),
+            FighterState::ShieldStun => Some(self.shieldstun_frames),
+            FighterState::Tech => Some(TECH_FRAMES),
+            FighterState::KnockedDown => Some(KNOCKED_DOWN_FRAMES),
+            _ => None,
+        }
+    }
+
+    // The first state frame the fighter can choose to do something else, or none if the
+    // state can't be acted out of at all.
+    pub fn first_actionable_frame(&self) -> Option<u32> {
+        match self.state {
+            FighterState::JumpSquat => Some(self.attributes.jump_squat_frames),
+            FighterState::Land => Some(2),
+            FighterState::LandSpecial => Some(self.attributes.land_special_frames.saturating_sub(1)),
+            FighterState::FallThrough => Some(self.ecb_air_transition_frames),
+            FighterState::AirDodge => None,
+            FighterState::Attack => self.attack().map(|attack| attack.total_frames),
+            FighterState::AerialAttack => self.attack().map(|attack| attack.total_frames),
+            FighterState::LandingLag => Some(self.landing_lag_frames.saturating_sub(1)),
+            FighterState::Hitstun => Some(self.hitstun_frames),
+            FighterState::ShieldStun => Some(self.shieldstun_frames),
+            FighterState::Tech => Some(TECH_FRAMES),
+            FighterState::KnockedDown => Some(KNOCKED_DOWN_FRAMES),
+            _ => Some(0),
         }
     }
 
+    pub fn frames_until_actionable(&self) -> Option<u32> {
+        self.first_actionable_frame().map(|frame| frame.saturating_sub(self.state_frame))
+    }
+
+    // The attack being done right now, if any.
+    pub fn attack(&self) -> Option<&Attack> {
+        if self.state != FighterState::Attack && self.state != FighterState::AerialAttack {
+            return None;
+        }
+        self.current_attack.and_then(|attack_index| self.attacks.get(attack_index))
+    }
+
+    // Anything that draws or checks hitboxes should go through here.
+    pub fn active_hitboxes(&self) -> Vec<&Hitbox> {
+        match self.attack() {
+            Some(attack) => attack.active_hitboxes(self.state_frame).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Hitbox offsets are for facing right.
+    pub fn hitbox_position(&self, hitbox: &Hitbox) -> Point {
+        Point {
+            x: self.position.x + self.facing_direction() * hitbox.offset.x,
+            y: self.position.y + hitbox.offset.y,
+        }
+    }
+
+    // The hurtbox is a capsule that fills the ECB from bottom to top.
+    pub fn hurtbox(&self) -> Hurtbox {
+        let radius = 0.5 * (self.ecb.right.x - self.ecb.left.x);
+        let bottom_y = self.position.y + self.ecb.bottom.y + radius;
+        let top_y = (self.position.y + self.ecb.top.y - radius).max(bottom_y);
+        Hurtbox {
+            bottom: Point { x: self.position.x, y: bottom_y },
+            top: Point { x: self.position.x, y: top_y },
+            radius,
+        }
+    }
+
+    pub fn is_invincible(&self) -> bool {
+        self.state == FighterState::Respawn || self.invincibility_frames > 0
+    }
+
+    // Puts the fighter back on the stage on top of a respawn platform after being KO'd.
+    pub fn respawn(&mut self, position: &Point, is_facing_right: bool) {
+        self.position = position.clone();
+        self.previous_position = position.clone();
+        self.velocity = Vector::default();
+        self.ground_angle = 0.0;
+        self.is_on_platform = false;
+        self.is_near_ledge = false;
+        self.air_jumps_left = self.attributes.air_jumps;
+        self.air_jump_rise_frames_left = 0;
+        self.float_frames_left = self.attributes.float_frames;
+        self.is_facing_right = is_facing_right;
+        self.was_facing_right = is_facing_right;
+        self.air_frames = 0;
+        self.ecb = self.ground_ecb.clone();
+        self.previous_ecb = self.ground_ecb.clone();
+        self.invincibility_frames = 0;
+        self.current_attack = None;
+        self.l_cancel_frames_left = 0;
+        self.knockback_velocity = Vector::default();
+        self.percent = 0.0;
+        self.change_state(FighterState::Respawn);
+    }
+
+    pub fn is_airborne(&self) -> bool {
+        self.state == FighterState::Airborne
+        || self.state == FighterState::AirDodge
+        || self.state == FighterState::FallThrough
+        || self.state == FighterState::Float
+        || self.state == FighterState::AerialAttack
+        || self.state == FighterState::Hitstun
+    }
+
+    // Fighters in these states stop at the edge of the ground instead of moving off of it.
+    pub fn can_teeter(&self) -> bool {
+        self.state == FighterState::Idle
+        || self.state == FighterState::Walk
+        || self.state == FighterState::Teeter
+    }
+
     pub fn can_land(&self) -> bool {
         self.state == FighterState::Airborne
         || self.state == FighterState::AirDodge
+        || self.state == FighterState::FallThrough
+        || self.state == FighterState::Float
+        || self.state == FighterState::AerialAttack
+        || self.state == FighterState::Hitstun
     }
 
+    // The ground angle should be set to the ground being landed on beforehand.
     pub fn land(&mut self) {
-        self.air_jumps_left = self.air_jumps;
+        // Horizontal momentum is kept, but redirected along the ground.
+        let ground_direction = self.ground_direction();
+        let ground_speed = self.velocity.x;
+        self.velocity.x = ground_direction.x * ground_speed;
+        self.velocity.y = ground_direction.y * ground_speed;
+
+        self.air_jumps_left = self.attributes.air_jumps;
+        self.air_jump_rise_frames_left = 0;
+        self.float_frames_left = self.attributes.float_frames;
+        self.knockback_velocity = Vector::default();
         match self.state {
             FighterState::Airborne => self.change_state(FighterState::Land),
+            FighterState::Hitstun => self.land_in_hitstun(),
+            FighterState::FallThrough => self.change_state(FighterState::Land),
+            FighterState::Float => self.change_state(FighterState::Land),
             FighterState::AirDodge => self.change_state(FighterState::LandSpecial),
+            FighterState::AerialAttack => self.land_aerial(),
             _ => ()
         }
     }
 
+    // Only knockback of 80 or more tumbles, which is 32 frames of hitstun or more. Landing
+    // from a tumble has to be teched or it knocks the fighter down.
+    fn land_in_hitstun(&mut self) {
+        if self.hitstun_frames < 32 {
+            self.change_state(FighterState::Land);
+        }
+        else if self.tech_frames_left > 0 {
+            self.velocity = Vector::default();
+            self.tech_frames_left = 0;
+            self.invincibility_frames = TECH_INVINCIBLE_FRAMES;
+            self.change_state(FighterState::Tech);
+        }
+        else {
+            self.velocity = Vector::default();
+            self.change_state(FighterState::KnockedDown);
+        }
+    }
+
+    // Pressing L, R or Z a little before landing an aerial cuts its landing lag.
+    fn land_aerial(&mut self) {
+        let is_l_cancelled = self.l_cancel_frames_left > 0;
+        if let Some(attack) = self.attack() {
+            self.landing_lag_frames = if is_l_cancelled {
+                attack.l_cancel_landing_lag
+            }
+            else {
+                attack.landing_lag
+            };
+        }
+        self.last_landing_was_l_cancelled = Some(is_l_cancelled);
+        self.l_cancel_frames_left = 0;
+        self.current_attack = None;
+        self.change_state(FighterState::LandingLag);
+    }
+
+    // Attacks come out when A is pressed. Each character only has one attack of each kind for now.
+    pub fn possible_attack(&self, kind: AttackKind) -> Option<usize> {
+        if !self.input.a_button.just_pressed() {
+            return None;
+        }
+        self.attacks.iter().position(|attack| attack.kind == kind)
+    }
+
+    fn start_attack(&mut self, attack_index: usize) {
+        self.current_attack = Some(attack_index);
+        self.hit_targets.clear();
+        match self.attacks[attack_index].kind {
+            AttackKind::Ground => self.change_state(FighterState::Attack),
+            AttackKind::Aerial => self.change_state(FighterState::AerialAttack),
+        }
+    }
+
+    // Launches the fighter. They can't do anything until hitstun is over, but the launch
+    // keeps slowing down after that.
+    pub fn take_hit(&mut self, damage: f64, launch_velocity: &Vector, hitstun_frames: u32) {
+        self.percent += damage;
+        self.velocity = Vector::default();
+        self.knockback_velocity = launch_velocity.clone();
+        self.hitstun_frames = hitstun_frames;
+        self.current_attack = None;
+        self.l_cancel_frames_left = 0;
+        self.tech_frames_left = 0;
+        self.change_state(FighterState::Hitstun);
+    }
+
+    // Shielded hits only push the fighter into shield stun.
+    pub fn take_shield_hit(&mut self, damage: f64) {
+        self.shieldstun_frames = ((damage + 4.45) / 2.235).floor() as u32;
+        self.change_state(FighterState::ShieldStun);
+    }
+
+    pub fn is_crouching(&self) -> bool {
+        self.state == FighterState::Crouch
+    }
+
+    pub fn is_shielding(&self) -> bool {
+        self.state == FighterState::Shield || self.state == FighterState::ShieldStun
+    }
+
     pub fn should_jump(&self) -> bool {
         self.input.x_button.just_pressed() || self.input.y_button.just_pressed()
     }
@@ -229,54 +539,103 @@ impl Fighter {
     pub fn y_axis_smashed(&self) -> bool {
         self.input.y_axis.value.abs() >= 0.6625 && self.input.y_axis.frames_active < 2
     }
+
+    pub fn should_fall_through(&self) -> bool {
+        self.is_on_platform && self.input.y_axis.value < 0.0 && self.y_axis_smashed()
+    }
+
+    // Holding the stick most of the way down crouches. Smashing it down on a platform
+    // falls through instead, which is checked first.
+    pub fn should_crouch(&self) -> bool {
+        self.input.y_axis.value <= -0.6125
+    }
+
+    pub fn should_shield(&self) -> bool {
+        self.input.l_button.is_pressed || self.input.r_button.is_pressed
+    }
 }
 
 // State update logic.
 impl Fighter {
     pub fn update(&mut self, input: &ControllerState) {
         self.input.update();
-        self.input.copy_inputs(&input);
+        self.input.copy_inputs(input);
         self.input.convert_to_melee_values();
 
+        if self.l_cancel_frames_left > 0 {
+            self.l_cancel_frames_left -= 1;
+        }
+        if self.tech_frames_left > 0 {
+            self.tech_frames_left -= 1;
+        }
+
         self.was_facing_right = self.is_facing_right;
         self.previous_position.x = self.position.x;
         self.previous_position.y = self.position.y;
 
         // Handle state transition.
-        //match self.state {
-        //    FighterState::Idle => self.state_idle_transition(),
-        //    FighterState::Turn => self.state_turn_transition(),
-        //    FighterState::Walk => self.state_walk_transition(),
-        //    FighterState::Dash => self.state_dash_transition(),
-        //    FighterState::Run => self.state_run_transition(),
-        //    FighterState::RunBrake => self.state_run_brake_transition(),
-        //    FighterState::RunTurn => self.state_run_turn_transition(),
-        //    FighterState::JumpSquat => self.state_jump_squat_transition(),
-        //    FighterState::Airborne => self.state_airborne_transition(),
-        //    FighterState::AirDodge => self.state_air_dodge_transition(),
-        //    FighterState::Land => self.state_land_transition(),
-        //    FighterState::LandSpecial => self.state_land_special_transition(),
-        //}
+        match self.state {
+            FighterState::Idle => self.state_idle_transition(),
+            FighterState::Turn => self.state_turn_transition(),
+            FighterState::Walk => self.state_walk_transition(),
+            FighterState::Dash => self.state_dash_transition(),
+            FighterState::Run => self.state_run_transition(),
+            FighterState::RunBrake => self.state_run_brake_transition(),
+            FighterState::RunTurn => self.state_run_turn_transition(),
+            FighterState::JumpSquat => self.state_jump_squat_transition(),
+            FighterState::Airborne => self.state_airborne_transition(),
+            FighterState::AirDodge => self.state_air_dodge_transition(),
+            FighterState::Land => self.state_land_transition(),
+            FighterState::LandSpecial => self.state_land_special_transition(),
+            FighterState::FallThrough => self.state_fall_through_transition(),
+            FighterState::Teeter => self.state_teeter_transition(),
+            FighterState::Respawn => self.state_respawn_transition(),
+            FighterState::Float => self.state_float_transition(),
+            FighterState::Attack => self.state_attack_transition(),
+            FighterState::AerialAttack => self.state_aerial_attack_transition(),
+            FighterState::LandingLag => self.state_landing_lag_transition(),
+            FighterState::Hitstun => self.state_hitstun_transition(),
+            FighterState::Crouch => self.state_crouch_transition(),
+            FighterState::Shield => self.state_shield_transition(),
+            FighterState::ShieldStun => self.state_shield_stun_transition(),
+            FighterState::Tech => self.state_tech_transition(),
+            FighterState::KnockedDown => self.state_knocked_down_transition(),
+        }
 
         // Handle state update.
-        //match self.state {
-        //    FighterState::Idle => self.state_idle_update(),
-        //    FighterState::Turn => self.state_turn_update(),
-        //    FighterState::Walk => self.state_walk_update(),
-        //    FighterState::Dash => self.state_dash_update(),
-        //    FighterState::Run => self.state_run_update(),
-        //    FighterState::RunBrake => self.state_run_brake_update(),
-        //    FighterState::RunTurn => self.state_run_turn_update(),
-        //    FighterState::JumpSquat => self.state_jump_squat_update(),
-        //    FighterState::Airborne => self.state_airborne_update(),
-        //    FighterState::AirDodge => self.state_air_dodge_update(),
-        //    FighterState::Land => self.state_land_update(),
-        //    FighterState::LandSpecial => self.state_land_special_update(),
-        //}
-
-        self.velocity.x += 0.05 * self.input.x_axis.value;
-        self.velocity.y += 0.05 * self.input.y_axis.value;
-        self.move_with_velocity();
+        match self.state {
+            FighterState::Idle => self.state_idle_update(),
+            FighterState::Turn => self.state_turn_update(),
+            FighterState::Walk => self.state_walk_update(),
+            FighterState::Dash => self.state_dash_update(),
+            FighterState::Run => self.state_run_update(),
+            FighterState::RunBrake => self.state_run_brake_update(),
+            FighterState::RunTurn => self.state_run_turn_update(),
+            FighterState::JumpSquat => self.state_jump_squat_update(),
+            FighterState::Airborne => self.state_airborne_update(),
+            FighterState::AirDodge => self.state_air_dodge_update(),
+            FighterState::Land => self.state_land_update(),
+            FighterState::LandSpecial => self.state_land_special_update(),
+            FighterState::FallThrough => self.state_fall_through_update(),
+            FighterState::Teeter => self.state_teeter_update(),
+            FighterState::Respawn => self.state_respawn_update(),
+            FighterState::Float => self.state_float_update(),
+            FighterState::Attack => self.state_attack_update(),
+            FighterState::AerialAttack => self.state_aerial_attack_update(),
+            FighterState::LandingLag => self.state_landing_lag_update(),
+            FighterState::Hitstun => self.state_hitstun_update(),
+            FighterState::Crouch => self.state_crouch_update(),
+            FighterState::Shield => self.state_shield_update(),
+            FighterState::ShieldStun => self.state_shield_stun_update(),
+            FighterState::Tech => self.state_tech_update(),
+            FighterState::KnockedDown => self.state_knocked_down_update(),
+        }
+
+        self.update_ecb();
+
+        if self.invincibility_frames > 0 {
+            self.invincibility_frames -= 1;
+        }
 
         self.state_frame += 1;
 
@@ -286,35 +645,88 @@ impl Fighter {
         //}
     }
 
+    // Grounded fighters keep the bottom of their ECB at their position. After leaving the ground
+    // the ECB smoothly shifts to its airborne shape, which keeps the ECB bottom low enough to
+    // wavedash out of jump squat. Keyframes for the current state override this.
+    fn update_ecb(&mut self) {
+        if self.is_airborne() {
+            self.air_frames += 1;
+        }
+        else {
+            self.air_frames = 0;
+        }
+
+        self.previous_ecb = self.ecb.clone();
+        self.ecb = self.target_ecb();
+    }
+
+    // Landing happens after the fighter has updated for the frame, so the ECB is changed to
+    // its grounded shape right away instead of waiting for the next update. The change still
+    // has to be swept against the stage afterward.
+    pub fn take_grounded_ecb(&mut self) {
+        self.air_frames = 0;
+        self.previous_ecb = self.ecb.clone();
+        self.ecb = self.target_ecb();
+    }
+
+    fn target_ecb(&self) -> ECB {
+        let air_interpolation = (self.air_frames as f64 / self.ecb_air_transition_frames.max(1) as f64).min(1.0);
+        let mut target_ecb = self.ground_ecb.lerp(&self.air_ecb, air_interpolation);
+
+        for keyframe in &self.ecb_keyframes {
+            if keyframe.state == self.state && keyframe.frame <= self.state_frame {
+                target_ecb = keyframe.ecb.clone();
+            }
+        }
+
+        target_ecb
+    }
+
     fn handle_horizontal_air_movement(&mut self) {
         if !self.input.x_axis.is_active() {
-            self.velocity.x += self.calculate_friction_delta(self.velocity.x, self.air_friction);
+            self.velocity.x += self.calculate_friction_delta(self.velocity.x, self.attributes.air_friction);
         }
         else {
             self.velocity.x += self.calculate_acceleration_delta(
                 self.velocity.x,
                 &self.input.x_axis,
-                self.air_base_acceleration,
-                self.air_axis_acceleration,
-                self.air_max_velocity,
-                self.air_friction,
+                self.attributes.air_base_acceleration,
+                self.attributes.air_axis_acceleration,
+                self.attributes.air_max_velocity,
+                self.attributes.air_friction,
             );
         }
     }
 
     fn handle_fast_fall(&mut self) {
         if self.velocity.y <= 0.0 && self.input.y_axis.value < 0.0 && self.y_axis_smashed() {
-            self.velocity.y = -self.fast_fall_velocity;
+            self.velocity.y = -self.attributes.fast_fall_velocity;
+        }
+    }
+
+    // Flutter jumps keep rising at the same speed for a while instead of slowing down right away.
+    fn handle_vertical_air_movement(&mut self) {
+        if self.air_jump_rise_frames_left > 0 {
+            self.velocity.y = self.air_jump_rise_velocity;
+            self.air_jump_rise_frames_left -= 1;
+        }
+        else {
+            self.handle_fast_fall();
+            self.handle_gravity();
         }
     }
 
     fn handle_gravity(&mut self) {
-        self.velocity.y -= self.gravity.min(self.fall_velocity + self.velocity.y).max(0.0);
+        self.velocity.y -= self.attributes.gravity.min(self.attributes.fall_velocity + self.velocity.y).max(0.0);
     }
 
+    // Knockback is kept apart from the fighter's own velocity since it slows down on its own.
     fn move_with_velocity(&mut self) {
-        self.position.x += self.velocity.x;
-        self.position.y += self.velocity.y;
+        self.position.x += self.velocity.x + self.knockback_velocity.x;
+        self.position.y += self.velocity.y + self.knockback_velocity.y;
+
+        let knockback_speed = (self.knockback_velocity.magnitude() - knockback::LAUNCH_SPEED_DECAY).max(0.0);
+        self.knockback_velocity.set_magnitude(knockback_speed);
     }
 
     fn apply_rotated_horizontal_velocity_change(&mut self, velocity_change: f64) {
@@ -328,7 +740,7 @@ impl Fighter {
     }
 
     fn apply_rotated_horizontal_friction(&mut self, friction: f64) {
-        self.apply_rotated_horizontal_velocity_change(self.calculate_friction_delta(self.velocity.x, friction));
+        self.apply_rotated_horizontal_velocity_change(self.calculate_friction_delta(self.ground_speed(), friction));
     }
 
     fn calculate_friction_delta(&self, velocity: f64, friction: f64) -> f64 {
@@ -373,13 +785,13 @@ impl Fighter {
 
         let max_acceleration = ((axis.direction() * max_velocity) - velocity).abs();
         let acceleration_abs = (base_acceleration + axis.value.abs() * axis_acceleration).min(max_acceleration);
-        return axis.direction() * acceleration_abs;
+        axis.direction() * acceleration_abs
     }
 }
 
 // ============ STATES ============
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum FighterState {
     Idle,
     Turn,
@@ -393,6 +805,19 @@ pub enum FighterState {
     AirDodge,
     Land,
     LandSpecial,
+    FallThrough,
+    Teeter,
+    Respawn,
+    Float,
+    Attack,
+    AerialAttack,
+    LandingLag,
+    Hitstun,
+    Crouch,
+    Shield,
+    ShieldStun,
+    Tech,
+    KnockedDown,
 }
 
 // Idle.
@@ -401,6 +826,18 @@ impl Fighter {
         if self.should_jump() {
             self.change_state(FighterState::JumpSquat);
         }
+        else if let Some(attack_index) = self.possible_attack(AttackKind::Ground) {
+            self.start_attack(attack_index);
+        }
+        else if self.should_fall_through() {
+            self.change_state(FighterState::FallThrough);
+        }
+        else if self.should_shield() {
+            self.change_state(FighterState::Shield);
+        }
+        else if self.should_crouch() {
+            self.change_state(FighterState::Crouch);
+        }
         else if self.x_axis_is_forward()
              && self.x_axis_smashed() {
             self.change_state(FighterState::Dash);
@@ -412,10 +849,13 @@ impl Fighter {
         else if self.x_axis_is_backward() {
             self.change_state(FighterState::Turn);
         }
+        else if self.is_near_ledge {
+            self.change_state(FighterState::Teeter);
+        }
     }
 
     fn state_idle_update(&mut self) {
-        //self.apply_rotated_horizontal_friction(self.ground_friction);
+        self.apply_rotated_horizontal_friction(self.attributes.ground_friction);
         self.move_with_velocity();
     }
 }
@@ -431,31 +871,26 @@ impl Fighter {
             self.change_state(FighterState::Dash);
         }
         else if self.x_axis_is_forward()
-             && self.state_frame >= self.turn_frames {
+             && self.state_frame >= self.attributes.turn_frames {
             self.change_state(FighterState::Walk);
         }
-        else if self.state_frame >= self.turn_frames {
+        else if self.state_frame >= self.attributes.turn_frames {
             self.change_state(FighterState::Idle);
         }
     }
 
     fn state_turn_update(&mut self) {
-        if self.state_frame == 0 {
-            match self.previous_state {
-                FighterState::Dash => {
-                    // I'm unsure where the 1.73 here comes from but it is necessary for now.
-                    self.apply_rotated_horizontal_velocity_change(-self.velocity.x.signum() * 1.73);
-                },
-                _ => ()
-            }
+        if self.state_frame == 0 && self.previous_state == FighterState::Dash {
+            // I'm unsure where the 1.73 here comes from but it is necessary for now.
+            self.apply_rotated_horizontal_velocity_change(-self.velocity.x.signum() * 1.73);
         }
 
         // Not quite right. Turn friction in melee applies on the first frame while walking,
         // but not during the one frame of turn seen while dash dancing. I need to implement that.
         if self.state_frame > 1 {
-            self.apply_rotated_horizontal_friction(2.0 * self.ground_friction);
+            self.apply_rotated_horizontal_friction(2.0 * self.attributes.ground_friction);
         }
-        if self.x_axis_is_backward() && self.state_frame == self.slow_dash_back_frames {
+        if self.x_axis_is_backward() && self.state_frame == self.attributes.slow_dash_back_frames {
             self.is_facing_right = self.input.x_axis.value >= 0.0;
         }
         self.move_with_velocity();
@@ -468,6 +903,18 @@ impl Fighter {
         if self.should_jump() {
             self.change_state(FighterState::JumpSquat);
         }
+        else if let Some(attack_index) = self.possible_attack(AttackKind::Ground) {
+            self.start_attack(attack_index);
+        }
+        else if self.should_fall_through() {
+            self.change_state(FighterState::FallThrough);
+        }
+        else if self.should_shield() {
+            self.change_state(FighterState::Shield);
+        }
+        else if self.should_crouch() {
+            self.change_state(FighterState::Crouch);
+        }
         else if self.x_axis_is_forward()
              && self.x_axis_smashed() {
             self.change_state(FighterState::Dash);
@@ -478,16 +925,14 @@ impl Fighter {
     }
 
     fn state_walk_update(&mut self) {
-        if self.state_frame == 0 {
-            if self.input.x_axis.is_active() {
-                self.apply_rotated_horizontal_velocity_change(self.facing_direction() * (0.1 + 0.2 * self.input.x_axis.value));
-            }
+        if self.state_frame == 0 && self.input.x_axis.is_active() {
+            self.apply_rotated_horizontal_velocity_change(self.facing_direction() * (0.1 + 0.2 * self.input.x_axis.value));
         }
 
-        let target_velocity = self.walk_max_velocity * self.input.x_axis.value;
+        let target_velocity = self.attributes.walk_max_velocity * self.input.x_axis.value;
 
         if self.velocity.x.abs() > target_velocity.abs() {
-            self.apply_rotated_horizontal_friction(2.0 * self.ground_friction);
+            self.apply_rotated_horizontal_friction(2.0 * self.attributes.ground_friction);
         }
         else if self.input.x_axis.is_active() && self.state_frame >= 1 {
             // This isn't quite right but close-ish, not sure what the real acceleration calculation is.
@@ -513,16 +958,16 @@ impl Fighter {
             self.change_state(FighterState::JumpSquat);
         }
         else if self.x_axis_is_forward()
-             && self.state_frame >= self.dash_max_frames {
+             && self.state_frame >= self.attributes.dash_max_frames {
             self.change_state(FighterState::Dash);
         }
         else if self.x_axis_is_forward()
-             && self.state_frame >= self.dash_min_frames
-             && self.state_frame < self.dash_max_frames {
+             && self.state_frame >= self.attributes.dash_min_frames
+             && self.state_frame < self.attributes.dash_max_frames {
             self.change_state(FighterState::Run);
         }
         else if !self.input.x_axis.is_active()
-             && self.state_frame >= self.dash_max_frames {
+             && self.state_frame >= self.attributes.dash_max_frames {
             self.change_state(FighterState::Idle);
         }
         else if self.x_axis_is_backward() {
@@ -545,22 +990,22 @@ impl Fighter {
                 self.dash_should_reset_velocity = false;
             }
 
-            self.apply_rotated_horizontal_velocity_change(self.dash_start_velocity * self.facing_direction());
-            if self.velocity.x.abs() > self.dash_max_velocity {
-                self.velocity.x = self.dash_max_velocity * self.facing_direction();
+            self.apply_rotated_horizontal_velocity_change(self.attributes.dash_start_velocity * self.facing_direction());
+            if self.velocity.x.abs() > self.attributes.dash_max_velocity {
+                self.velocity.x = self.attributes.dash_max_velocity * self.facing_direction();
             }
         }
 
         if self.state_frame >= 1 {
             if !self.input.x_axis.is_active() {
-                self.apply_rotated_horizontal_friction(self.ground_friction);
+                self.apply_rotated_horizontal_friction(self.attributes.ground_friction);
             }
             else {
                 self.apply_rotated_horizontal_acceleration(
-                    self.dash_base_acceleration,
-                    self.dash_axis_acceleration,
-                    self.dash_max_velocity,
-                    self.ground_friction,
+                    self.attributes.dash_base_acceleration,
+                    self.attributes.dash_axis_acceleration,
+                    self.attributes.dash_max_velocity,
+                    self.attributes.ground_friction,
                 );
             }
         }
@@ -583,9 +1028,9 @@ impl Fighter {
     }
 
     fn state_run_update(&mut self) {
-        let run_acceleration = ((self.dash_max_velocity * self.input.x_axis.value) - self.velocity.x)
-                             * (1.0 / (2.5 * self.dash_max_velocity))
-                             * (self.dash_axis_acceleration + (self.dash_base_acceleration / self.input.x_axis.value.abs()));
+        let run_acceleration = ((self.attributes.dash_max_velocity * self.input.x_axis.value) - self.velocity.x)
+                             * (1.0 / (2.5 * self.attributes.dash_max_velocity))
+                             * (self.attributes.dash_axis_acceleration + (self.attributes.dash_base_acceleration / self.input.x_axis.value.abs()));
         self.apply_rotated_horizontal_velocity_change(run_acceleration);
         self.move_with_velocity();
     }
@@ -598,21 +1043,21 @@ impl Fighter {
             self.change_state(FighterState::JumpSquat);
         }
         else if self.x_axis_is_backward()
-             && self.state_frame >= self.run_brake_frames {
+             && self.state_frame >= self.attributes.run_brake_frames {
             self.change_state(FighterState::Turn);
         }
         else if self.x_axis_is_backward()
-             && self.state_frame < self.run_brake_frames {
+             && self.state_frame < self.attributes.run_brake_frames {
             self.change_state(FighterState::RunTurn);
         }
         else if !self.input.x_axis.is_active()
-             && self.state_frame >= self.run_brake_frames {
+             && self.state_frame >= self.attributes.run_brake_frames {
             self.change_state(FighterState::Idle);
         }
     }
 
     fn state_run_brake_update(&mut self) {
-        self.apply_rotated_horizontal_friction(self.ground_friction);
+        self.apply_rotated_horizontal_friction(self.attributes.ground_friction);
         self.move_with_velocity();
     }
 }
@@ -658,20 +1103,20 @@ impl Fighter {
         if !self.input.x_axis.is_active()
         || (!self.run_turn_has_fully_turned && self.x_axis_is_forward())
         || (self.run_turn_has_fully_turned && self.x_axis_is_backward()) {
-            self.apply_rotated_horizontal_friction(self.ground_friction);
+            self.apply_rotated_horizontal_friction(self.attributes.ground_friction);
         }
         else {
             self.apply_rotated_horizontal_acceleration(
-                self.dash_base_acceleration,
-                self.dash_axis_acceleration,
-                self.dash_max_velocity,
-                self.ground_friction,
+                self.attributes.dash_base_acceleration,
+                self.attributes.dash_axis_acceleration,
+                self.attributes.dash_max_velocity,
+                self.attributes.ground_friction,
             );
         }
 
         self.move_with_velocity();
 
-        if self.run_turn_has_fully_turned || (self.run_turn_melee_frame < 9 && !self.run_turn_has_fully_turned) {
+        if self.run_turn_has_fully_turned || self.run_turn_melee_frame < 9 {
             self.run_turn_melee_frame += 1;
         }
     }
@@ -681,16 +1126,16 @@ impl Fighter {
 impl Fighter {
     fn state_jump_squat_transition(&mut self) {
         if (self.input.l_button.is_pressed || self.input.r_button.is_pressed)
-        && self.state_frame >= self.jump_squat_frames {
+        && self.state_frame >= self.attributes.jump_squat_frames {
             self.change_state(FighterState::AirDodge);
         }
-        else if self.state_frame >= self.jump_squat_frames {
+        else if self.state_frame >= self.attributes.jump_squat_frames {
             self.change_state(FighterState::Airborne);
         }
     }
 
     fn state_jump_squat_update(&mut self) {
-        self.apply_rotated_horizontal_friction(2.0 * self.ground_friction);
+        self.apply_rotated_horizontal_friction(2.0 * self.attributes.ground_friction);
         self.move_with_velocity();
     }
 }
@@ -701,43 +1146,71 @@ impl Fighter {
         if self.input.l_button.just_pressed() || self.input.r_button.just_pressed() {
             self.change_state(FighterState::AirDodge);
         }
+        else if let Some(attack_index) = self.possible_attack(AttackKind::Aerial) {
+            self.start_attack(attack_index);
+        }
+        else if self.should_float() {
+            self.change_state(FighterState::Float);
+        }
     }
 
     fn state_airborne_update(&mut self) {
-        if self.state_frame == 0 {
-            match self.previous_state {
-                FighterState::JumpSquat => {
-                    // Handle changing horizontal velocity when jumping off of the ground based on stick x axis.
-                    self.velocity.x = (self.velocity.x * self.jump_velocity_dampening) + (self.input.x_axis.value * self.jump_start_horizontal_velocity);
-                    if self.velocity.x.abs() > self.jump_max_horizontal_velocity {
-                        self.velocity.x = self.velocity.x.signum() * self.jump_max_horizontal_velocity;
-                    }
-
-                    // Handle short hopping and full hopping.
-                    if self.jump_is_active() {
-                        self.velocity.y = self.full_hop_velocity;
-                    }
-                    else {
-                        self.velocity.y = self.short_hop_velocity;
-                    }
-                },
-                _ => ()
+        if self.state_frame == 0 && self.previous_state == FighterState::JumpSquat {
+            // Handle changing horizontal velocity when jumping off of the ground based on stick x axis.
+            self.velocity.x = (self.velocity.x * self.attributes.jump_velocity_dampening) + (self.input.x_axis.value * self.attributes.jump_start_horizontal_velocity);
+            if self.velocity.x.abs() > self.attributes.jump_max_horizontal_velocity {
+                self.velocity.x = self.velocity.x.signum() * self.attributes.jump_max_horizontal_velocity;
+            }
+
+            // Handle short hopping and full hopping.
+            if self.jump_is_active() {
+                self.velocity.y = self.attributes.full_hop_velocity;
+            }
+            else {
+                self.velocity.y = self.attributes.short_hop_velocity;
             }
         }
 
         if self.state_frame >= 1 {
             // Handle air jumps.
             if self.should_jump() && self.air_jumps_left > 0 {
-                self.velocity.x = self.input.x_axis.value * self.air_jump_horizontal_axis_multiplier;
-                self.velocity.y = self.full_hop_velocity * self.air_jump_velocity_multiplier;
-                self.air_jumps_left -= 1;
+                self.air_jump();
             }
             self.handle_horizontal_air_movement();
-            self.handle_fast_fall();
-            self.handle_gravity();
+            self.handle_vertical_air_movement();
         }
         self.move_with_velocity();
     }
+
+    // Each air jump after the first is a bit weaker than the one before it. Neither the decay
+    // nor the steady rise comes from Melee, they're stand-ins until there's data to go on.
+    fn air_jump(&mut self) {
+        let air_jumps_used = self.attributes.air_jumps - self.air_jumps_left;
+        let decay = self.attributes.air_jump_velocity_decay.powi(air_jumps_used as i32);
+        let jump_velocity = self.attributes.full_hop_velocity * self.attributes.air_jump_velocity_multiplier * decay;
+
+        self.velocity.x = self.input.x_axis.value * self.attributes.air_jump_horizontal_axis_multiplier;
+        if self.attributes.air_jump_rise_frames > 0 {
+            // Rise at a steady speed that covers about the same height a normal jump would.
+            let jump_height = jump_velocity * jump_velocity / (2.0 * self.attributes.gravity);
+            self.air_jump_rise_velocity = jump_height / self.attributes.air_jump_rise_frames as f64;
+            self.air_jump_rise_frames_left = self.attributes.air_jump_rise_frames;
+            self.velocity.y = self.air_jump_rise_velocity;
+        }
+        else {
+            self.velocity.y = jump_velocity;
+        }
+        self.air_jumps_left -= 1;
+    }
+
+    // Holding jump once the fighter starts falling makes them float if they can.
+    fn should_float(&self) -> bool {
+        self.float_frames_left > 0
+        && self.state_frame >= 1
+        && self.velocity.y <= 0.0
+        && self.jump_is_active()
+        && !self.should_jump()
+    }
 }
 
 // AirDodge.
@@ -785,18 +1258,7 @@ impl Fighter {
     }
 
     fn state_land_update(&mut self) {
-        if self.state_frame == 0 {
-            self.velocity.y = 0.0;
-            //let mut velocity_change_vector = Vector {
-            //    x: 0.0,
-            //    y: -self.velocity.y,
-            //};
-            //velocity_change_vector.rotate(self.ground_angle);
-            //self.velocity.x += velocity_change_vector.x;
-            //self.velocity.y += velocity_change_vector.y;
-        }
-
-        //self.apply_rotated_horizontal_friction(self.ground_friction);
+        self.apply_rotated_horizontal_friction(self.attributes.ground_friction);
         self.move_with_velocity();
     }
 }
@@ -804,36 +1266,302 @@ impl Fighter {
 // LandSpecial.
 impl Fighter {
     fn state_land_special_transition(&mut self) {
-        if self.should_jump() && self.state_frame >= 9 {
+        self.landing_lag_transition(self.attributes.land_special_frames);
+    }
+
+    // The frame of landing is the first frame of lag, and it has already gone by
+    // without counting as a state frame.
+    fn landing_lag_transition(&mut self, lag_frames: u32) {
+        let lag_is_over = self.state_frame + 1 >= lag_frames;
+        if self.should_jump() && lag_is_over {
             self.change_state(FighterState::JumpSquat);
         }
         else if self.x_axis_is_forward()
              && self.x_axis_smashed()
-             && self.state_frame >= 9 {
+             && lag_is_over {
             self.change_state(FighterState::Dash);
         }
         else if self.x_axis_is_forward()
              && !self.x_axis_smashed()
-             && self.state_frame >= 9 {
+             && lag_is_over {
             self.change_state(FighterState::Walk);
         }
         else if self.x_axis_is_backward()
-             && self.state_frame >= 9 {
+             && lag_is_over {
             self.change_state(FighterState::Turn);
         }
         else if !self.input.x_axis.is_active()
-             && self.state_frame >= 9 {
+             && lag_is_over {
             self.change_state(FighterState::Idle);
         }
     }
 
     fn state_land_special_update(&mut self) {
+        let friction_multiplier = if self.state_frame < 3 { 2.0 } else { 1.0 };
+        self.apply_rotated_horizontal_friction(friction_multiplier * self.attributes.ground_friction);
+        self.move_with_velocity();
+    }
+}
+
+// Attack.
+impl Fighter {
+    fn state_attack_transition(&mut self) {
+        let total_frames = self.attack().map_or(0, |attack| attack.total_frames);
+        if self.state_frame >= total_frames {
+            self.current_attack = None;
+            self.change_state(FighterState::Idle);
+        }
+    }
+
+    fn state_attack_update(&mut self) {
+        self.apply_rotated_horizontal_friction(self.attributes.ground_friction);
+        self.move_with_velocity();
+    }
+}
+
+// AerialAttack.
+impl Fighter {
+    fn state_aerial_attack_transition(&mut self) {
+        let total_frames = self.attack().map_or(0, |attack| attack.total_frames);
+        if self.state_frame >= total_frames {
+            self.current_attack = None;
+            self.change_state(FighterState::Airborne);
+        }
+    }
+
+    fn state_aerial_attack_update(&mut self) {
+        if self.input.l_button.just_pressed()
+        || self.input.r_button.just_pressed()
+        || self.input.z_button.just_pressed() {
+            self.l_cancel_frames_left = L_CANCEL_WINDOW_FRAMES;
+        }
+        self.handle_horizontal_air_movement();
+        self.handle_vertical_air_movement();
+        self.move_with_velocity();
+    }
+}
+
+// LandingLag.
+impl Fighter {
+    fn state_landing_lag_transition(&mut self) {
+        self.landing_lag_transition(self.landing_lag_frames);
+    }
+
+    fn state_landing_lag_update(&mut self) {
+        self.apply_rotated_horizontal_friction(self.attributes.ground_friction);
+        self.move_with_velocity();
+    }
+}
+
+// Hitstun.
+impl Fighter {
+    fn state_hitstun_transition(&mut self) {
+        if self.state_frame >= self.hitstun_frames {
+            self.change_state(FighterState::Airborne);
+        }
+    }
+
+    // Gravity comes after moving so the fighter follows the knockback preview exactly.
+    fn state_hitstun_update(&mut self) {
+        // DI is read on the first frame of hitstun since there is no hitlag yet.
+        if self.state_frame == 0 {
+            let stick = Vector {
+                x: self.input.x_axis.value,
+                y: self.input.y_axis.value,
+            };
+            self.knockback_velocity = knockback::apply_di(&self.knockback_velocity, &stick);
+        }
+        if self.input.l_button.just_pressed() || self.input.r_button.just_pressed() {
+            self.tech_frames_left = TECH_WINDOW_FRAMES;
+        }
+        self.move_with_velocity();
+        self.handle_gravity();
+    }
+}
+
+// Crouch.
+impl Fighter {
+    fn state_crouch_transition(&mut self) {
+        if self.should_jump() {
+            self.change_state(FighterState::JumpSquat);
+        }
+        else if let Some(attack_index) = self.possible_attack(AttackKind::Ground) {
+            self.start_attack(attack_index);
+        }
+        else if self.should_fall_through() {
+            self.change_state(FighterState::FallThrough);
+        }
+        else if self.should_shield() {
+            self.change_state(FighterState::Shield);
+        }
+        else if !self.should_crouch() {
+            self.change_state(FighterState::Idle);
+        }
+    }
+
+    fn state_crouch_update(&mut self) {
+        self.apply_rotated_horizontal_friction(self.attributes.ground_friction);
+        self.move_with_velocity();
+    }
+}
+
+// Shield.
+impl Fighter {
+    fn state_shield_transition(&mut self) {
+        if self.should_jump() {
+            self.change_state(FighterState::JumpSquat);
+        }
+        else if !self.should_shield() {
+            self.change_state(FighterState::Idle);
+        }
+    }
+
+    fn state_shield_update(&mut self) {
+        self.apply_rotated_horizontal_friction(self.attributes.ground_friction);
+        self.move_with_velocity();
+    }
+}
+
+// ShieldStun.
+impl Fighter {
+    fn state_shield_stun_transition(&mut self) {
+        if self.state_frame >= self.shieldstun_frames {
+            self.change_state(FighterState::Shield);
+        }
+    }
+
+    fn state_shield_stun_update(&mut self) {
+        self.apply_rotated_horizontal_friction(self.attributes.ground_friction);
+        self.move_with_velocity();
+    }
+}
+
+// Tech.
+impl Fighter {
+    fn state_tech_transition(&mut self) {
+        if self.state_frame >= TECH_FRAMES {
+            self.change_state(FighterState::Idle);
+        }
+    }
+
+    fn state_tech_update(&mut self) {
+        self.apply_rotated_horizontal_friction(self.attributes.ground_friction);
+        self.move_with_velocity();
+    }
+}
+
+// KnockedDown.
+impl Fighter {
+    fn state_knocked_down_transition(&mut self) {
+        if self.state_frame >= KNOCKED_DOWN_FRAMES {
+            self.change_state(FighterState::Idle);
+        }
+    }
+
+    fn state_knocked_down_update(&mut self) {
+        self.apply_rotated_horizontal_friction(self.attributes.ground_friction);
+        self.move_with_velocity();
+    }
+}
+
+// FallThrough.
+impl Fighter {
+    fn state_fall_through_transition(&mut self) {
+        // Platforms are ignored until the ECB is done shifting into its air shape,
+        // otherwise the bottom of the ECB could rise back above the platform.
+        if self.air_frames >= self.ecb_air_transition_frames {
+            self.change_state(FighterState::Airborne);
+        }
+    }
+
+    fn state_fall_through_update(&mut self) {
         if self.state_frame == 0 {
             self.velocity.y = 0.0;
         }
 
-        let friction_multiplier = if self.state_frame < 3 { 2.0 } else { 1.0 };
-        self.apply_rotated_horizontal_friction(friction_multiplier * self.ground_friction);
+        if self.state_frame >= 1 {
+            self.handle_fast_fall();
+        }
+        self.handle_horizontal_air_movement();
+        self.handle_gravity();
         self.move_with_velocity();
     }
 }
+
+// Teeter.
+impl Fighter {
+    fn state_teeter_transition(&mut self) {
+        if self.should_jump() {
+            self.change_state(FighterState::JumpSquat);
+        }
+        else if let Some(attack_index) = self.possible_attack(AttackKind::Ground) {
+            self.start_attack(attack_index);
+        }
+        else if self.should_fall_through() {
+            self.change_state(FighterState::FallThrough);
+        }
+        else if self.should_shield() {
+            self.change_state(FighterState::Shield);
+        }
+        else if self.should_crouch() {
+            self.change_state(FighterState::Crouch);
+        }
+        else if self.x_axis_is_forward()
+             && self.x_axis_smashed() {
+            self.change_state(FighterState::Dash);
+        }
+        else if self.x_axis_is_backward() {
+            self.change_state(FighterState::Turn);
+        }
+        else if !self.is_near_ledge {
+            self.change_state(FighterState::Idle);
+        }
+    }
+
+    fn state_teeter_update(&mut self) {
+        self.apply_rotated_horizontal_friction(self.attributes.ground_friction);
+        self.move_with_velocity();
+    }
+}
+
+// Float.
+impl Fighter {
+    fn state_float_transition(&mut self) {
+        // There is only one float per trip through the air.
+        if self.input.l_button.just_pressed() || self.input.r_button.just_pressed() {
+            self.float_frames_left = 0;
+            self.change_state(FighterState::AirDodge);
+        }
+        else if !self.jump_is_active() || self.float_frames_left == 0 {
+            self.float_frames_left = 0;
+            self.change_state(FighterState::Airborne);
+        }
+    }
+
+    fn state_float_update(&mut self) {
+        self.handle_horizontal_air_movement();
+        self.velocity.y = 0.0;
+        self.float_frames_left -= 1;
+        self.move_with_velocity();
+    }
+}
+
+// Respawn.
+impl Fighter {
+    fn state_respawn_transition(&mut self) {
+        // The platform goes away once the fighter moves off of it or it times out,
+        // and the fighter stays invincible for a little while after.
+        if self.input.x_axis.is_active()
+        || self.input.y_axis.is_active()
+        || self.should_jump()
+        || self.state_frame >= self.respawn_platform_frames {
+            self.invincibility_frames = self.respawn_invincibility_frames;
+            self.change_state(FighterState::Airborne);
+        }
+    }
+
+    fn state_respawn_update(&mut self) {
+        self.velocity.x = 0.0;
+        self.velocity.y = 0.0;
+    }
+}
@@ -1,184 +1,1801 @@
+use std::f64::consts::PI;
+
 use crate::point_math::Point;
-use crate::line_math::{PolyLine, LineSegment};
+use crate::line_math::LineSegment;
 use crate::vector_math::Vector;
 use crate::controller_state::ControllerState;
-use crate::fighter::Fighter;
+use crate::fighter::{Fighter, FighterState};
+use crate::collision_line::{CollisionLine, CollisionPolyLine};
+use crate::stage::Stage;
+use crate::match_rules::{MatchRules, MatchResults, PlayerResults};
+use crate::attack::Hitbox;
+use crate::knockback;
 
+#[derive(Clone)]
 pub struct FightingGame {
+    pub players: Vec<Player>,
+    pub stage: Stage,
+    pub rules: MatchRules,
+    pub match_state: MatchState,
+    pub match_state_frame: u32,
+    pub match_frame: u32,
+    pub paused_by: usize,
+    pub was_quit: bool,
+    pub results: Option<MatchResults>,
+    pub frame_advantage: Option<FrameAdvantage>,
+    pub advantage_tracker: Option<AdvantageTracker>,
+}
+
+// How many frames sooner the attacker could act than the defender after the last hit
+// or shielded hit, or later if it is negative.
+#[derive(Clone)]
+pub struct FrameAdvantage {
+    pub attacker: usize,
+    pub defender: usize,
+    pub on_shield: bool,
+    pub frames: i64,
+}
+
+// Follows a hit until both fighters can act again.
+#[derive(Clone)]
+pub struct AdvantageTracker {
+    pub attacker: usize,
+    pub defender: usize,
+    pub on_shield: bool,
+    pub attacker_actionable_frame: Option<u32>,
+    pub defender_actionable_frame: Option<u32>,
+}
+
+#[derive(Clone)]
+pub struct Player {
+    pub fighter: Fighter,
     pub input: ControllerState,
-    pub player: Fighter,
-    pub is_paused: bool,
-    pub collision_poly_lines: Vec<PolyLine>,
+    pub stocks: u32,
+    pub falls: u32,
+    pub kos: u32,
+    pub l_cancels: u32,
+    pub l_cancel_landings: u32,
+    pub damage_dealt: f64,
+    pub last_hit_by: Option<usize>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum MatchState {
+    Countdown,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum ECBPoint {
+    Bottom,
+    Left,
+    Top,
+    Right,
+}
+
+struct Collision {
+    time: f64,
+    normal: Vector,
+    line_velocity: Vector,
+    ground_angle: f64,
+    is_ground: bool,
+    is_pass_through: bool,
 }
 
 impl FightingGame {
-    pub fn default() -> Self {
+    // Each fighter starts at the stage's spawn point for their player number.
+    pub fn new(rules: MatchRules, stage: Stage, fighters: Vec<Fighter>) -> Self {
+        let players = fighters.into_iter().enumerate()
+            .map(|(player_index, mut fighter)| {
+                let spawn_point = &stage.spawn_points[player_index % stage.spawn_points.len()];
+                fighter.position = spawn_point.position.clone();
+                fighter.previous_position = spawn_point.position.clone();
+                fighter.is_facing_right = spawn_point.is_facing_right;
+                Player {
+                    fighter,
+                    input: ControllerState::default(),
+                    stocks: rules.stock_count,
+                    falls: 0,
+                    kos: 0,
+                    l_cancels: 0,
+                    l_cancel_landings: 0,
+                    damage_dealt: 0.0,
+                    last_hit_by: None,
+                }
+            })
+            .collect();
+
         Self{
-            input: ControllerState::default(),
-            player: Fighter::fox(),
-            is_paused: false,
-            collision_poly_lines: vec![
-                PolyLine::from_points(&vec![
-                    Point { x: -56.0, y: -3.5 },
-                    Point { x: -28.0, y: 12.5 },
-                    Point { x: -15.0, y: -5.5 },
-                    Point { x: -4.0, y: 0.0 },
-
-                    Point { x: 4.0, y: 0.0 },
-                    Point { x: 15.0, y: -5.5 },
-                    Point { x: 28.0, y: 12.5 },
-                    Point { x: 56.0, y: -3.5 },
-                ])
-            ],
-//            collision_poly_lines: vec![
-//                PolyLine::from_points(&vec![
-//                    Point { x: -54.0, y: -100.0 },
-//                    Point { x: -54.0, y: -47.0 },
-//                    Point { x: -53.0, y: -46.0 },
-//                    Point { x: -53.0, y: -31.0 },
-//                    Point { x: -54.0, y: -30.0 },
-//                    Point { x: -54.0, y: -28.0 },
-//                    Point { x: -53.0, y: -27.0 },
-//                    Point { x: -53.0, y: -12.0 },
-//                    Point { x: -54.0, y: -11.0 },
-//                    Point { x: -55.0, y: -8.0 },
-//                    Point { x: -56.0, y: -7.0 },
-//
-//                    Point { x: -56.0, y: -3.5 },
-//                    Point { x: -39.0, y: 0.0 },
-//                    Point { x: 39.0, y: 0.0 },
-//                    Point { x: 56.0, y: -3.5 },
-//
-//                    Point { x: 56.0, y: -7.0 },
-//                    Point { x: 55.0, y: -8.0 },
-//                    Point { x: 54.0, y: -11.0 },
-//                    Point { x: 53.0, y: -12.0 },
-//                    Point { x: 53.0, y: -27.0 },
-//                    Point { x: 54.0, y: -28.0 },
-//                    Point { x: 54.0, y: -30.0 },
-//                    Point { x: 53.0, y: -31.0 },
-//                    Point { x: 53.0, y: -46.0 },
-//                    Point { x: 54.0, y: -47.0 },
-//                    Point { x: 54.0, y: -100.0 },
-//
-//                    Point { x: -54.0, y: -100.0 },
-//                ])
-//            ],
-        }
-    }
-
-    pub fn update(&mut self, input: &ControllerState) {
-        self.input.update();
-        self.input.copy_inputs(input);
-        self.input.convert_to_melee_values();
-
-        let mut frame_advance = false;
-
-        if self.input.start_button.just_pressed() {
-            self.is_paused = !self.is_paused;
-        }
-        if self.is_paused && self.input.z_button.just_pressed() {
-            frame_advance = true;
-        }
-
-        if !self.is_paused || frame_advance {
-            self.player.update(&self.input);
-            self.resolve_collisions();
-        }
-    }
-
-    fn resolve_collisions(&mut self) {
-        for poly_line in &self.collision_poly_lines {
-            for line_segment in &poly_line.segments {
-                // Ground collision.
-                let possible_collision = self.get_ground_line_collision_position(
-                    &self.player,
-                    line_segment,
-                );
-                if let Some(collision_position) = possible_collision {
-                    //if self.player.can_land() && self.player.velocity.dot(&line_segment.normal()) <= 0.0 {
-                    if self.player.velocity.dot(&line_segment.normal()) <= 0.0 {
-                        self.player.position.x = collision_position.x;
-                        self.player.position.y = collision_position.y;
-
-                        let collision_normal = line_segment.normal();
-                        let normal_component = self.player.velocity.dot(&collision_normal);
-                        self.player.velocity.x -= normal_component * collision_normal.x;
-                        self.player.velocity.y -= normal_component * collision_normal.y;
-
-                        //self.player.ground_angle = line_segment.direction().angle();
-                        //self.player.land()
-                    }
+            players,
+            stage,
+            rules,
+            match_state: MatchState::Countdown,
+            match_state_frame: 0,
+            match_frame: 0,
+            paused_by: 0,
+            was_quit: false,
+            results: None,
+            frame_advantage: None,
+            advantage_tracker: None,
+        }
+    }
+
+    pub fn change_match_state(&mut self, new_state: MatchState) {
+        self.match_state_frame = 0;
+        self.match_state = new_state;
+        if new_state == MatchState::GameOver {
+            self.results = Some(self.collect_results());
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.match_state == MatchState::Paused
+    }
+
+    pub fn frames_left(&self) -> Option<u32> {
+        self.rules.time_limit_frames().map(|limit| limit.saturating_sub(self.match_frame))
+    }
+
+    // Whoever has the most stocks left wins. Ties and quit matches have no winner,
+    // and there is no winner until the game is over.
+    pub fn winner(&self) -> Option<usize> {
+        if self.match_state != MatchState::GameOver || self.was_quit {
+            return None;
+        }
+        let most_stocks = self.players.iter().map(|player| player.stocks).max().unwrap_or(0);
+        if most_stocks == 0 {
+            return None;
+        }
+        let mut players_with_most_stocks = self.players.iter().enumerate()
+            .filter(|(_, player)| player.stocks == most_stocks)
+            .map(|(player_index, _)| player_index);
+        match (players_with_most_stocks.next(), players_with_most_stocks.next()) {
+            (Some(player_index), None) => Some(player_index),
+            _ => None,
+        }
+    }
+
+    fn collect_results(&self) -> MatchResults {
+        MatchResults {
+            winner: self.winner(),
+            was_quit: self.was_quit,
+            frames_played: self.match_frame,
+            players: self.players.iter()
+                .map(|player| PlayerResults {
+                    stocks_remaining: player.stocks,
+                    kos: player.kos,
+                    falls: player.falls,
+                    damage_dealt: player.damage_dealt,
+                    l_cancel_rate: if player.l_cancel_landings > 0 {
+                        Some(player.l_cancels as f64 / player.l_cancel_landings as f64)
+                    } else {
+                        None
+                    },
+                })
+                .collect(),
+        }
+    }
+
+    // Each player is driven by the input with the same index. Players without
+    // an input just stand still.
+    pub fn update(&mut self, inputs: &[&ControllerState]) {
+        let idle_input = ControllerState::default();
+        for (player_index, player) in self.players.iter_mut().enumerate() {
+            player.input.update();
+            player.input.copy_inputs(inputs.get(player_index).copied().unwrap_or(&idle_input));
+            player.input.convert_to_melee_values();
+        }
+
+        match self.match_state {
+            MatchState::Countdown => self.state_countdown_update(),
+            MatchState::Playing => self.state_playing_update(),
+            MatchState::Paused => self.state_paused_update(),
+            MatchState::GameOver => (),
+        }
+
+        self.match_state_frame += 1;
+    }
+
+    fn state_countdown_update(&mut self) {
+        let countdown_frames = 180;
+        if self.match_state_frame + 1 >= countdown_frames {
+            self.change_match_state(MatchState::Playing);
+        }
+    }
+
+    fn state_playing_update(&mut self) {
+        let possible_pausing_player = self.players.iter()
+            .position(|player| player.stocks > 0 && player.input.start_button.just_pressed());
+        if let Some(player_index) = possible_pausing_player {
+            self.paused_by = player_index;
+            self.change_match_state(MatchState::Paused);
+            return;
+        }
+
+        self.advance_frame();
+    }
+
+    // Only the player who paused can unpause, frame advance, or quit.
+    fn state_paused_update(&mut self) {
+        let input = &self.players[self.paused_by].input;
+        if input.start_button.just_pressed() {
+            if input.l_button.is_pressed && input.r_button.is_pressed && input.a_button.is_pressed {
+                self.was_quit = true;
+                self.change_match_state(MatchState::GameOver);
+            }
+            else {
+                self.change_match_state(MatchState::Playing);
+            }
+        }
+        else if input.z_button.just_pressed() {
+            self.advance_frame();
+        }
+    }
+
+    fn advance_frame(&mut self) {
+        // Find what everyone is standing on before the stage moves so they can move with it.
+        let ridden_poly_lines: Vec<Option<usize>> = self.players.iter()
+            .map(|player| Self::find_ridden_poly_line(&player.fighter, &self.stage.collision_poly_lines))
+            .collect();
+
+        self.stage.update();
+        let collision_poly_lines = &self.stage.collision_poly_lines;
+
+        for (player, ridden_poly_line) in self.players.iter_mut().zip(ridden_poly_lines) {
+            if player.stocks == 0 {
+                continue;
+            }
+            let fighter = &mut player.fighter;
+            fighter.update(&player.input);
+
+            // Moving ground carries the fighters standing on it, and they keep
+            // its velocity when they leave it.
+            let possible_ground_movement = ridden_poly_line
+                .map(|poly_line_index| collision_poly_lines[poly_line_index].movement.clone());
+            if let Some(ground_movement) = &possible_ground_movement {
+                fighter.position.x += ground_movement.x;
+                fighter.position.y += ground_movement.y;
+            }
+
+            Self::resolve_collisions(fighter, collision_poly_lines);
+
+            if let Some(ground_movement) = &possible_ground_movement {
+                if fighter.is_airborne() {
+                    fighter.velocity.x += ground_movement.x;
+                    fighter.velocity.y += ground_movement.y;
+                }
+            }
+
+            // Landing lag only comes from landing during an aerial, and it starts on the frame of landing.
+            if player.fighter.state == FighterState::LandingLag && player.fighter.state_frame == 0 {
+                player.l_cancel_landings += 1;
+                if player.fighter.last_landing_was_l_cancelled == Some(true) {
+                    player.l_cancels += 1;
                 }
             }
         }
+        self.handle_hits();
+        self.track_frame_advantage();
+
+        // Count this frame before anything can end the match so the results include it.
+        self.match_frame += 1;
+        self.handle_blast_zones();
+        if self.match_state != MatchState::GameOver && self.frames_left() == Some(0) {
+            self.change_match_state(MatchState::GameOver);
+        }
     }
 
-    fn get_ground_line_collision_position(
-        &self,
-        player: &Fighter,
-        ground_line: &LineSegment,
-    ) -> Option<Point> {
+    fn find_ridden_poly_line(fighter: &Fighter, collision_poly_lines: &[CollisionPolyLine]) -> Option<usize> {
+        if fighter.is_airborne() || fighter.state == FighterState::Respawn {
+            return None;
+        }
 
         let tolerance = 0.01;
+        let ecb_bottom = Self::ecb_point_position(fighter, &fighter.ecb.bottom);
+        Self::find_ground_under_point(fighter, &ecb_bottom, tolerance, false, collision_poly_lines)
+            .map(|(_, poly_line_index, _)| poly_line_index)
+    }
 
-        let ecb_bottom_x_previous = player.ecb.bottom.x + player.previous_position.x;
-        let ecb_bottom_y_previous = player.ecb.bottom.y + player.previous_position.y;
-        let ecb_bottom_x = player.ecb.bottom.x + player.position.x;
-        let ecb_bottom_y = player.ecb.bottom.y + player.position.y;
+    // Every fighter moves before anything is hit, so trades are possible. An attack only
+    // hits each fighter once, with the first of its hitboxes that touches them.
+    fn handle_hits(&mut self) {
+        let mut hits: Vec<(usize, usize, Hitbox)> = Vec::new();
+        for (attacker_index, attacker) in self.players.iter().enumerate() {
+            if attacker.stocks == 0 {
+                continue;
+            }
+            let attacker_fighter = &attacker.fighter;
+            for hitbox in attacker_fighter.active_hitboxes() {
+                let hitbox_position = attacker_fighter.hitbox_position(hitbox);
+                for (defender_index, defender) in self.players.iter().enumerate() {
+                    let is_valid_target = defender_index != attacker_index
+                        && defender.stocks > 0
+                        && !defender.fighter.is_invincible()
+                        && !attacker_fighter.hit_targets.contains(&defender_index)
+                        && !hits.iter().any(|(hit_attacker, hit_defender, _)| *hit_attacker == attacker_index && *hit_defender == defender_index);
+                    if is_valid_target && defender.fighter.hurtbox().overlaps(&hitbox_position, hitbox.radius) {
+                        hits.push((attacker_index, defender_index, hitbox.clone()));
+                    }
+                }
+            }
+        }
 
-        let movement_vector = Vector {
-            x: ecb_bottom_x - ecb_bottom_x_previous,
-            y: ecb_bottom_y - ecb_bottom_y_previous,
-        };
-        let movement_vector_direction = movement_vector.direction();
-
-        let movement_line = LineSegment {
-            point_a: Point {
-                x: ecb_bottom_x_previous,
-                y: ecb_bottom_y_previous,
-            },
-            point_b: Point {
-                x: ecb_bottom_x,
-                y: ecb_bottom_y,
-            },
+        // Shielded hits don't do damage, and crouching takes a third off of the knockback.
+        for (attacker_index, defender_index, hitbox) in hits {
+            self.players[attacker_index].fighter.hit_targets.push(defender_index);
+            self.advantage_tracker = Some(AdvantageTracker {
+                attacker: attacker_index,
+                defender: defender_index,
+                on_shield: self.players[defender_index].fighter.is_shielding(),
+                attacker_actionable_frame: None,
+                defender_actionable_frame: None,
+            });
+            if self.players[defender_index].fighter.is_shielding() {
+                self.players[defender_index].fighter.take_shield_hit(hitbox.damage);
+                continue;
+            }
+
+            let direction = self.players[attacker_index].fighter.facing_direction();
+            self.players[defender_index].last_hit_by = Some(attacker_index);
+            let defender = &mut self.players[defender_index].fighter;
+            let mut knockback = knockback::calculate_knockback(&hitbox, defender.percent, defender.attributes.weight);
+            if defender.is_crouching() {
+                knockback *= 2.0 / 3.0;
+            }
+            let launch_velocity = knockback::calculate_launch_velocity(knockback, hitbox.angle, direction);
+            let hitstun_frames = (knockback * knockback::HITSTUN_PER_KNOCKBACK).floor() as u32;
+            defender.take_hit(hitbox.damage, &launch_velocity, hitstun_frames);
+
+            self.players[attacker_index].damage_dealt += hitbox.damage;
+        }
+    }
+
+    // Frame advantage is the difference between the first frames each fighter can act on
+    // after the latest hit. It stays up until the next one.
+    fn track_frame_advantage(&mut self) {
+        let tracker = match &mut self.advantage_tracker {
+            Some(tracker) => tracker,
+            None => return,
         };
+        let is_actionable = |player: &Player| player.fighter.frames_until_actionable() == Some(0);
+        if tracker.attacker_actionable_frame.is_none() && is_actionable(&self.players[tracker.attacker]) {
+            tracker.attacker_actionable_frame = Some(self.match_frame);
+        }
+        if tracker.defender_actionable_frame.is_none() && is_actionable(&self.players[tracker.defender]) {
+            tracker.defender_actionable_frame = Some(self.match_frame);
+        }
+
+        if let (Some(attacker_frame), Some(defender_frame)) = (tracker.attacker_actionable_frame, tracker.defender_actionable_frame) {
+            self.frame_advantage = Some(FrameAdvantage {
+                attacker: tracker.attacker,
+                defender: tracker.defender,
+                on_shield: tracker.on_shield,
+                frames: defender_frame as i64 - attacker_frame as i64,
+            });
+            self.advantage_tracker = None;
+        }
+    }
+
+    // Fighters that leave the blast zone lose a stock and respawn if they have any left.
+    // The KO goes to whoever hit them last, so falling without being hit is a self destruct.
+    fn handle_blast_zones(&mut self) {
+        for player_index in 0..self.players.len() {
+            let player = &mut self.players[player_index];
+            if player.stocks == 0 || self.stage.blast_zone.contains(&player.fighter.position) {
+                continue;
+            }
+
+            player.stocks -= 1;
+            player.falls += 1;
+            if self.advantage_tracker.as_ref().is_some_and(|tracker| tracker.defender == player_index) {
+                self.advantage_tracker = None;
+            }
+            if player.stocks > 0 {
+                let respawn_points = &self.stage.respawn_points;
+                let respawn_point = &respawn_points[player_index % respawn_points.len()];
+                player.fighter.respawn(&respawn_point.position, respawn_point.is_facing_right);
+            }
+            if let Some(attacker_index) = player.last_hit_by.take() {
+                self.players[attacker_index].kos += 1;
+            }
+        }
+
+        // A solo game ends when the player runs out of stocks, otherwise it ends
+        // when there is only one player left.
+        let players_with_stocks = self.players.iter().filter(|player| player.stocks > 0).count();
+        let players_needed = if self.players.len() > 1 { 2 } else { 1 };
+        if players_with_stocks < players_needed {
+            self.change_match_state(MatchState::GameOver);
+        }
+    }
+
+    // Moves the player from its previous position to its current position one collision
+    // at a time. The earliest collision across every line is found, the player is moved
+    // up to it, and the rest of the movement slides along whatever was hit. Lines that are
+    // moving carry the player along while they slide.
+    fn resolve_collisions(player: &mut Fighter, collision_poly_lines: &[CollisionPolyLine]) {
+        let max_iterations = 4;
 
-        // Extend the movement line backward by some tolerance to prevent,
-        // moving through obstacles.
-        let movement_line_with_tolerance = LineSegment {
-            point_a: Point {
-                x: ecb_bottom_x_previous - movement_vector_direction.x * tolerance,
-                y: ecb_bottom_y_previous - movement_vector_direction.y * tolerance,
-            },
-            point_b: Point {
-                x: ecb_bottom_x,
-                y: ecb_bottom_y,
-            },
+        // Fighters on the respawn platform are held in place above the stage.
+        if player.state == FighterState::Respawn {
+            return;
+        }
+
+        let was_airborne = player.is_airborne();
+        let mut movement = Vector {
+            x: player.position.x - player.previous_position.x,
+            y: player.position.y - player.previous_position.y,
         };
+        let movement_distance = movement.magnitude();
+        player.position = player.previous_position.clone();
+
+        Self::resolve_ecb_change(player, 1.0, collision_poly_lines);
 
-        if movement_line_with_tolerance.intersects_with(ground_line) {
-            if let Some(intersection_point) = movement_line_with_tolerance.intersection_with(ground_line) {
-                let penetration_vector = Vector {
-                    x: movement_line.point_b.x - intersection_point.x,
-                    y: movement_line.point_b.y - intersection_point.y,
+        // The fraction of the frame that hasn't been moved through yet.
+        let mut remaining_time = 1.0;
+
+        for _ in 0..max_iterations {
+            let possible_collision = Self::find_earliest_collision(
+                player,
+                &movement,
+                remaining_time,
+                collision_poly_lines,
+            );
+            if let Some(collision) = possible_collision {
+                player.position.x += movement.x * collision.time;
+                player.position.y += movement.y * collision.time;
+                movement.x *= 1.0 - collision.time;
+                movement.y *= 1.0 - collision.time;
+                remaining_time *= 1.0 - collision.time;
+
+                // Removing the normal component slides the rest of the movement along what was hit.
+                // This is done relative to the line so a moving line keeps pushing the player.
+                let line_movement = Vector {
+                    x: collision.line_velocity.x * remaining_time,
+                    y: collision.line_velocity.y * remaining_time,
                 };
-                let ground_line_direction = ground_line.direction();
-                let projection_length = penetration_vector.dot(&ground_line_direction);
-                let collision_delta_x = ground_line_direction.x * projection_length;
-                let collision_delta_y = ground_line_direction.y * projection_length;
-                let projected_point = Point {
-                    x: intersection_point.x + collision_delta_x,
-                    y: intersection_point.y + collision_delta_y,
+                let relative_movement = Vector {
+                    x: movement.x - line_movement.x,
+                    y: movement.y - line_movement.y,
                 };
-                Some(projected_point)
+                let movement_normal_component = relative_movement.dot(&collision.normal);
+                if movement_normal_component < 0.0 {
+                    movement.x -= movement_normal_component * collision.normal.x;
+                    movement.y -= movement_normal_component * collision.normal.y;
+                }
+
+                if collision.is_ground {
+                    player.is_on_platform = collision.is_pass_through;
+                    player.ground_angle = collision.ground_angle;
+                }
+
+                // Landing redirects the velocity along the ground itself, which is what
+                // conserves momentum when wavedashing or wavelanding. Grounded velocity
+                // is relative to the ground, since moving ground carries the player.
+                if collision.is_ground && player.can_land() {
+                    player.velocity.x -= collision.line_velocity.x;
+                    player.velocity.y -= collision.line_velocity.y;
+                    player.land();
+
+                    // The ECB takes its grounded shape on the frame of landing, which puts
+                    // the player's position on the ground right away.
+                    player.take_grounded_ecb();
+                    Self::resolve_ecb_change(player, remaining_time, collision_poly_lines);
+                }
+                else {
+                    let relative_velocity = Vector {
+                        x: player.velocity.x - collision.line_velocity.x,
+                        y: player.velocity.y - collision.line_velocity.y,
+                    };
+                    let velocity_normal_component = relative_velocity.dot(&collision.normal);
+                    if velocity_normal_component < 0.0 {
+                        player.velocity.x -= velocity_normal_component * collision.normal.x;
+                        player.velocity.y -= velocity_normal_component * collision.normal.y;
+                    }
+                }
             }
             else {
-                None
+                player.position.x += movement.x;
+                player.position.y += movement.y;
+                break;
+            }
+        }
+
+        if !was_airborne && !player.is_airborne() {
+            Self::follow_ground(player, movement_distance, collision_poly_lines);
+        }
+        if !player.is_airborne() {
+            Self::update_is_near_ledge(player, collision_poly_lines);
+        }
+    }
+
+    // Keeps grounded fighters on the ground when they move over the corner between two lines.
+    // Ground that turns downward too sharply can't be followed, so the fighter leaves it
+    // and becomes airborne instead. Ground that turns upward is already handled by the
+    // collision sweep.
+    fn follow_ground(
+        player: &mut Fighter,
+        movement_distance: f64,
+        collision_poly_lines: &[CollisionPolyLine],
+    ) {
+
+        let tolerance = 0.01;
+        let max_downward_angle_change = 0.25 * PI;
+
+        // Ground up to 60 degrees steep can drop away by a bit less than twice the distance moved.
+        let max_snap_distance = 2.0 * movement_distance + tolerance;
+
+        let ecb_bottom = Self::ecb_point_position(player, &player.ecb.bottom);
+        let possible_ground = Self::find_ground_under_point(
+            player,
+            &ecb_bottom,
+            max_snap_distance,
+            false,
+            collision_poly_lines,
+        );
+
+        if let Some((ground_y, _, ground_line)) = &possible_ground {
+            let ground_speed = player.ground_speed();
+            let new_ground_angle = ground_line.segment.direction().angle();
+            let downward_angle_change = (player.ground_angle - new_ground_angle) * ground_speed.signum();
+
+            if downward_angle_change <= max_downward_angle_change {
+                player.position.y += *ground_y - ecb_bottom.y;
+                player.ground_angle = new_ground_angle;
+                player.is_on_platform = ground_line.is_pass_through;
+
+                let ground_direction = player.ground_direction();
+                player.velocity.x = ground_direction.x * ground_speed;
+                player.velocity.y = ground_direction.y * ground_speed;
+                return;
+            }
+        }
+
+        // Walking fighters stop at the end of the ground they were on, everything
+        // else moves off of it with the velocity it had.
+        if possible_ground.is_none() && player.can_teeter() {
+            let previous_ecb_bottom = Point {
+                x: player.previous_position.x + player.previous_ecb.bottom.x,
+                y: player.previous_position.y + player.previous_ecb.bottom.y,
+            };
+            let possible_previous_ground = Self::find_ground_under_point(
+                player,
+                &previous_ecb_bottom,
+                tolerance,
+                true,
+                collision_poly_lines,
+            );
+            if let Some((_, _, previous_ground_line)) = possible_previous_ground {
+                let line_segment = &previous_ground_line.segment;
+                let ground_end = if ecb_bottom.x > previous_ecb_bottom.x {
+                    line_segment.right_point()
+                }
+                else {
+                    line_segment.left_point()
+                };
+                player.position.x = ground_end.x - player.ecb.bottom.x;
+                player.position.y = ground_end.y - player.ecb.bottom.y;
+                player.velocity = Vector::default();
+                player.ground_angle = line_segment.direction().angle();
+                player.is_on_platform = previous_ground_line.is_pass_through;
+                player.is_near_ledge = true;
+                if player.state != FighterState::Teeter {
+                    player.change_state(FighterState::Teeter);
+                }
+                return;
             }
         }
+
+        player.is_on_platform = false;
+        player.is_near_ledge = false;
+        player.change_state(FighterState::Airborne);
+    }
+
+    // A fighter is near a ledge when the side of their ECB that they are facing hangs
+    // over the end of the ground.
+    fn update_is_near_ledge(player: &mut Fighter, collision_poly_lines: &[CollisionPolyLine]) {
+        let tolerance = 0.01;
+
+        let ecb_front_distance = if player.is_facing_right {
+            player.ecb.right.x
+        }
         else {
-            None
+            -player.ecb.left.x
+        };
+        let ecb_bottom = Self::ecb_point_position(player, &player.ecb.bottom);
+        // Allow the ground ahead to slope up or down as steeply as ground can be.
+        let max_ground_slope_distance = 2.0 * ecb_front_distance;
+        let probe_point = Point {
+            x: ecb_bottom.x + player.facing_direction() * ecb_front_distance,
+            y: ecb_bottom.y + max_ground_slope_distance,
+        };
+
+        player.is_near_ledge = Self::find_ground_under_point(
+            player,
+            &probe_point,
+            2.0 * max_ground_slope_distance + tolerance,
+            false,
+            collision_poly_lines,
+        ).is_none();
+    }
+
+    // Returns the height, poly line index, and line of the highest ground that is directly
+    // under the point, or just barely above it, within the given distance. The point can be
+    // checked against where the lines were at the start of the frame instead, in which case
+    // the height is relative to where the lines are now.
+    fn find_ground_under_point<'a>(
+        player: &Fighter,
+        point: &Point,
+        max_distance: f64,
+        is_start_of_frame: bool,
+        collision_poly_lines: &'a [CollisionPolyLine],
+    ) -> Option<(f64, usize, &'a CollisionLine)> {
+
+        let tolerance = 0.01;
+
+        let mut highest_ground: Option<(f64, usize, &CollisionLine)> = None;
+        for (poly_line_index, poly_line) in collision_poly_lines.iter().enumerate() {
+            let point = if is_start_of_frame {
+                Point {
+                    x: point.x + poly_line.movement.x,
+                    y: point.y + poly_line.movement.y,
+                }
+            }
+            else {
+                point.clone()
+            };
+
+            for collision_line in &poly_line.lines {
+                if !Self::ecb_point_can_hit_line(player, ECBPoint::Bottom, collision_line) {
+                    continue;
+                }
+
+                let line_segment = &collision_line.segment;
+                let left_point = line_segment.left_point();
+                let right_point = line_segment.right_point();
+                if point.x < left_point.x - tolerance || point.x > right_point.x + tolerance {
+                    continue;
+                }
+
+                let clamped_x = point.x.max(left_point.x).min(right_point.x);
+                let ground_y = left_point.y + line_segment.slope() * (clamped_x - left_point.x);
+                let distance = point.y - ground_y;
+                if distance < -tolerance || distance > max_distance {
+                    continue;
+                }
+
+                let is_higher = match highest_ground {
+                    Some((highest_y, _, _)) => ground_y > highest_y,
+                    None => true,
+                };
+                if is_higher {
+                    highest_ground = Some((ground_y, poly_line_index, collision_line));
+                }
+            }
+        }
+
+        highest_ground
+    }
+
+    // Changes in the shape of the ECB are swept like movement, so an ECB that grows
+    // pushes the player out of lines instead of through them. This happens at the start
+    // of the frame, before moving lines have moved, and again when landing part of the
+    // way through the frame.
+    fn resolve_ecb_change(player: &mut Fighter, remaining_time: f64, collision_poly_lines: &[CollisionPolyLine]) {
+        let ecb_point_changes = [
+            (ECBPoint::Bottom, player.previous_ecb.bottom.clone(), player.ecb.bottom.clone()),
+            (ECBPoint::Left, player.previous_ecb.left.clone(), player.ecb.left.clone()),
+            (ECBPoint::Top, player.previous_ecb.top.clone(), player.ecb.top.clone()),
+            (ECBPoint::Right, player.previous_ecb.right.clone(), player.ecb.right.clone()),
+        ];
+
+        for (ecb_point, previous_offset, offset) in &ecb_point_changes {
+            let start = Self::ecb_point_position(player, previous_offset);
+            let change = Vector {
+                x: offset.x - previous_offset.x,
+                y: offset.y - previous_offset.y,
+            };
+
+            let mut earliest_hit_time: Option<f64> = None;
+            for poly_line in collision_poly_lines {
+                let relative_start = Point {
+                    x: start.x + poly_line.movement.x * remaining_time,
+                    y: start.y + poly_line.movement.y * remaining_time,
+                };
+                for collision_line in &poly_line.lines {
+                    if !Self::ecb_point_can_hit_line(player, *ecb_point, collision_line) {
+                        continue;
+                    }
+                    let line_segment = &collision_line.segment;
+                    if let Some(time) = Self::sweep_point_against_line(&relative_start, &change, line_segment) {
+                        let is_earlier = match earliest_hit_time {
+                            Some(earliest_time) => time < earliest_time,
+                            None => true,
+                        };
+                        if is_earlier {
+                            earliest_hit_time = Some(time);
+                        }
+                    }
+                }
+            }
+
+            // Pushing back against the change keeps the point exactly where it hit the line,
+            // rather than sliding it sideways into neighboring lines.
+            if let Some(time) = earliest_hit_time {
+                player.position.x -= change.x * (1.0 - time);
+                player.position.y -= change.y * (1.0 - time);
+            }
+        }
+    }
+
+    // Ground is hit by the bottom of the ECB, ceilings by the top, and lines facing
+    // sideways by the side of the ECB facing them. This includes the sloped parts of
+    // ground and ceilings so the sides of the ECB can't slip into small ledges in walls.
+    // Ground can never be steep enough to reach the sides of the ECB while standing on it.
+    fn ecb_point_can_hit_line(
+        player: &Fighter,
+        ecb_point: ECBPoint,
+        collision_line: &CollisionLine,
+    ) -> bool {
+
+        if collision_line.is_pass_through {
+            return ecb_point == ECBPoint::Bottom
+                && collision_line.is_ground()
+                && player.state != FighterState::FallThrough;
+        }
+
+        let line_normal = collision_line.segment.normal();
+        match ecb_point {
+            ECBPoint::Bottom => collision_line.is_ground(),
+            ECBPoint::Left => line_normal.x > 0.0,
+            ECBPoint::Top => collision_line.is_ceiling(),
+            ECBPoint::Right => line_normal.x < 0.0,
+        }
+    }
+
+    // The corners of solid lines are also checked against the edges of the ECB
+    // so the ECB can't slip past them. Ties go to whichever line comes first
+    // so collisions are deterministic.
+    fn find_earliest_collision(
+        player: &Fighter,
+        movement: &Vector,
+        remaining_time: f64,
+        collision_poly_lines: &[CollisionPolyLine],
+    ) -> Option<Collision> {
+
+        let mut earliest_collision: Option<Collision> = None;
+        let mut keep_if_earlier = |collision: Collision| {
+            let is_earlier = match &earliest_collision {
+                Some(earliest) => collision.time < earliest.time,
+                None => true,
+            };
+            if is_earlier {
+                earliest_collision = Some(collision);
+            }
+        };
+
+        for poly_line in collision_poly_lines {
+            // Sweeping against a moving line is the same as sweeping against a still line
+            // with the movement relative to it. The lines are already where they end up
+            // this frame, so the ECB is moved to where it is relative to them.
+            let line_movement = Vector {
+                x: poly_line.movement.x * remaining_time,
+                y: poly_line.movement.y * remaining_time,
+            };
+            let relative_movement = Vector {
+                x: movement.x - line_movement.x,
+                y: movement.y - line_movement.y,
+            };
+            let relative_ecb_point_position = |ecb_point: &Point| Point {
+                x: player.position.x + ecb_point.x + line_movement.x,
+                y: player.position.y + ecb_point.y + line_movement.y,
+            };
+
+            let ecb_bottom = relative_ecb_point_position(&player.ecb.bottom);
+            let ecb_left = relative_ecb_point_position(&player.ecb.left);
+            let ecb_top = relative_ecb_point_position(&player.ecb.top);
+            let ecb_right = relative_ecb_point_position(&player.ecb.right);
+            let ecb_points = [
+                (ECBPoint::Bottom, &ecb_bottom),
+                (ECBPoint::Left, &ecb_left),
+                (ECBPoint::Top, &ecb_top),
+                (ECBPoint::Right, &ecb_right),
+            ];
+            let ecb_edges = [
+                (&ecb_bottom, &ecb_right),
+                (&ecb_right, &ecb_top),
+                (&ecb_top, &ecb_left),
+                (&ecb_left, &ecb_bottom),
+            ];
+
+            for collision_line in &poly_line.lines {
+                let line_segment = &collision_line.segment;
+
+                for (ecb_point, ecb_point_position) in &ecb_points {
+                    if !Self::ecb_point_can_hit_line(player, *ecb_point, collision_line) {
+                        continue;
+                    }
+                    if let Some(time) = Self::sweep_point_against_line(ecb_point_position, &relative_movement, line_segment) {
+                        keep_if_earlier(Collision {
+                            time,
+                            normal: line_segment.normal(),
+                            line_velocity: poly_line.movement.clone(),
+                            ground_angle: line_segment.direction().angle(),
+                            is_ground: *ecb_point == ECBPoint::Bottom,
+                            is_pass_through: collision_line.is_pass_through,
+                        });
+                    }
+                }
+
+                if collision_line.is_pass_through {
+                    continue;
+                }
+
+                for corner in &[&line_segment.point_a, &line_segment.point_b] {
+                    for (edge_start, edge_end) in &ecb_edges {
+                        let possible_hit = Self::sweep_ecb_edge_against_corner(
+                            edge_start,
+                            edge_end,
+                            &relative_movement,
+                            corner,
+                        );
+                        if let Some((time, normal)) = possible_hit {
+                            keep_if_earlier(Collision {
+                                time,
+                                normal,
+                                line_velocity: poly_line.movement.clone(),
+                                ground_angle: 0.0,
+                                is_ground: false,
+                                is_pass_through: false,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        earliest_collision
+    }
+
+    fn ecb_point_position(player: &Fighter, ecb_point: &Point) -> Point {
+        Point {
+            x: player.position.x + ecb_point.x,
+            y: player.position.y + ecb_point.y,
+        }
+    }
+
+    // Returns the fraction of the movement at which the point crosses the front of the line.
+    // Points that are already slightly behind the line still count so that an ECB that starts
+    // grounded and moves downward in the same frame, like when wavedashing out of jump squat,
+    // is still detected.
+    fn sweep_point_against_line(
+        point: &Point,
+        movement: &Vector,
+        line: &LineSegment,
+    ) -> Option<f64> {
+
+        let tolerance = 0.01;
+        let minimum_approach_speed = 0.000001;
+
+        let line_normal = line.normal();
+        let approach_speed = -movement.dot(&line_normal);
+        if approach_speed <= minimum_approach_speed {
+            return None;
+        }
+
+        let start_distance = Vector {
+            x: point.x - line.point_a.x,
+            y: point.y - line.point_a.y,
+        }.dot(&line_normal);
+        if start_distance < -tolerance {
+            return None;
+        }
+
+        let time = (start_distance / approach_speed).max(0.0);
+        if time > 1.0 {
+            return None;
+        }
+
+        let hit_distance_along_line = Vector {
+            x: point.x + movement.x * time - line.point_a.x,
+            y: point.y + movement.y * time - line.point_a.y,
+        }.dot(&line.direction());
+        if hit_distance_along_line < -tolerance
+        || hit_distance_along_line > line.length() + tolerance {
+            return None;
+        }
+
+        Some(time)
+    }
+
+    // Sweeping an ECB edge into a corner is the same as sweeping the corner backward
+    // into the ECB edge. Returns the fraction of the movement at which they touch along with
+    // the normal to push the ECB away with. Hits on the ends of the edge are left to
+    // sweep_point_against_line.
+    fn sweep_ecb_edge_against_corner(
+        edge_start: &Point,
+        edge_end: &Point,
+        movement: &Vector,
+        corner: &Point,
+    ) -> Option<(f64, Vector)> {
+
+        let tolerance = 0.01;
+        let minimum_approach_speed = 0.000001;
+
+        let edge = LineSegment {
+            point_a: edge_start.clone(),
+            point_b: edge_end.clone(),
+        };
+
+        // The ECB winds counter clockwise so the outward normal is the
+        // inverse of the line normal.
+        let edge_outward_normal = edge.normal().inverse();
+        let approach_speed = movement.dot(&edge_outward_normal);
+        if approach_speed <= minimum_approach_speed {
+            return None;
+        }
+
+        let start_distance = Vector {
+            x: corner.x - edge_start.x,
+            y: corner.y - edge_start.y,
+        }.dot(&edge_outward_normal);
+        if start_distance < -tolerance {
+            return None;
+        }
+
+        let time = (start_distance / approach_speed).max(0.0);
+        if time > 1.0 {
+            return None;
+        }
+
+        let hit_distance_along_edge = Vector {
+            x: corner.x - movement.x * time - edge_start.x,
+            y: corner.y - movement.y * time - edge_start.y,
+        }.dot(&edge.direction());
+        if hit_distance_along_edge <= tolerance
+        || hit_distance_along_edge >= edge.length() - tolerance {
+            return None;
+        }
+
+        Some((time, edge_outward_normal.inverse()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn load_stage(name: &str) -> Stage {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join("stages").join(format!("{}.json", name));
+        Stage::from_file(&path).unwrap()
+    }
+
+    // Fox's wavedash from SmashWiki: Wavedash (SSBM) and Fox (SSBM). The air dodge goes 3.1
+    // along the stick snapped to (0.95, -0.275) and keeps 0.9 of it on landing, which is
+    // 2.68. LandSpecial lasts 10 frames and doubles Fox's 0.08 traction for its first 3.
+    // Worked out by hand that's 1 frame at 2.68, 3 frames losing 0.16, then 27 frames
+    // losing 0.08, so 31 frames of sliding and 38.9192 units.
+    const FOX_WAVEDASH_DISTANCE: f64 = 38.9192;
+    const FOX_WAVEDASH_SLIDE_FRAMES: u32 = 31;
+    // The distance above is rounded to four places.
+    const FOX_WAVEDASH_TOLERANCE: f64 = 1e-4;
+
+    // Jumps, then air dodges down and forward as soon as jump squat ends.
+    #[test]
+    fn fox_wavedash_distance_and_lag() {
+        let mut game = FightingGame::new(MatchRules::default(), load_stage("final_destination"), vec![Fighter::fox()]);
+        game.change_match_state(MatchState::Playing);
+        let start_x = game.players[0].fighter.position.x;
+        let ground_y = game.players[0].fighter.position.y;
+
+        let mut input = ControllerState::default();
+        input.x_button.is_pressed = true;
+        game.update(&[&input]);
+        input.x_button.is_pressed = false;
+        for _ in 0..2 {
+            game.update(&[&input]);
+        }
+        input.x_axis.value = 1.0;
+        input.y_axis.value = -0.3;
+        input.r_button.is_pressed = true;
+        game.update(&[&input]);
+
+        let fighter = &game.players[0].fighter;
+        assert_eq!(fighter.state, FighterState::LandSpecial);
+        assert!((fighter.position.y - ground_y).abs() < 1e-9, "landed at y {}", fighter.position.y);
+
+        // The landing frame already slides, so it counts for both.
+        let idle_input = ControllerState::default();
+        let mut land_special_frames = 1;
+        let mut slide_frames = 1;
+        for _ in 0..60 {
+            game.update(&[&idle_input]);
+            let fighter = &game.players[0].fighter;
+            if fighter.state == FighterState::LandSpecial {
+                land_special_frames += 1;
+            }
+            if fighter.velocity.x > 0.0 {
+                slide_frames += 1;
+            }
+        }
+        assert_eq!(land_special_frames, 10);
+        assert_eq!(slide_frames, FOX_WAVEDASH_SLIDE_FRAMES);
+        let distance = game.players[0].fighter.position.x - start_x;
+        assert!((distance - FOX_WAVEDASH_DISTANCE).abs() < FOX_WAVEDASH_TOLERANCE, "wavedashed {}", distance);
+    }
+
+    // Short hops, neutral airs right away and lands during it. Pressing L a few frames before
+    // landing is an L-cancel. Returns how many frames of landing lag there were.
+    fn short_hop_neutral_air(game: &mut FightingGame, should_l_cancel: bool) -> u32 {
+        let ground_y = game.players[0].fighter.position.y;
+        let mut input = ControllerState::default();
+        input.x_button.is_pressed = true;
+        game.update(&[&input]);
+        input.x_button.is_pressed = false;
+        while game.players[0].fighter.state != FighterState::Airborne {
+            game.update(&[&input]);
+        }
+        input.a_button.is_pressed = true;
+        game.update(&[&input]);
+        input.a_button.is_pressed = false;
+        assert_eq!(game.players[0].fighter.state, FighterState::AerialAttack);
+
+        while game.players[0].fighter.state == FighterState::AerialAttack {
+            let fighter = &game.players[0].fighter;
+            if should_l_cancel && fighter.velocity.y < 0.0 && fighter.position.y - ground_y < 8.0 {
+                input.l_button.is_pressed = true;
+            }
+            game.update(&[&input]);
+        }
+        assert_eq!(game.players[0].fighter.state, FighterState::LandingLag);
+
+        let idle_input = ControllerState::default();
+        let mut landing_lag_frames = 1;
+        while game.players[0].fighter.state == FighterState::LandingLag {
+            game.update(&[&idle_input]);
+            if game.players[0].fighter.state == FighterState::LandingLag {
+                landing_lag_frames += 1;
+            }
+        }
+        landing_lag_frames
+    }
+
+    // Fox's neutral air has 15 frames of landing lag, or 7 when it is L-cancelled.
+    // One of the two landings is L-cancelled, so the rate is half.
+    #[test]
+    fn l_cancel_rate_counts_aerial_landings() {
+        let mut game = FightingGame::new(MatchRules::default(), load_stage("final_destination"), vec![Fighter::fox()]);
+        game.change_match_state(MatchState::Playing);
+
+        assert_eq!(short_hop_neutral_air(&mut game, true), 7);
+        assert_eq!(game.players[0].fighter.last_landing_was_l_cancelled, Some(true));
+        assert_eq!(short_hop_neutral_air(&mut game, false), 15);
+        assert_eq!(game.players[0].fighter.last_landing_was_l_cancelled, Some(false));
+        assert_eq!(game.players[0].l_cancels, 1);
+        assert_eq!(game.players[0].l_cancel_landings, 2);
+
+        game.change_match_state(MatchState::GameOver);
+        let results = game.results.as_ref().unwrap();
+        assert_eq!(results.players[0].l_cancel_rate, Some(0.5));
+    }
+
+    // Two Foxes close enough for player 1's up smash to hit player 2.
+    fn foxes_up_close() -> FightingGame {
+        let mut game = FightingGame::new(MatchRules::default(), load_stage("final_destination"), vec![Fighter::fox(), Fighter::fox()]);
+        game.change_match_state(MatchState::Playing);
+        let defender_x = game.players[0].fighter.position.x + 5.0;
+        game.players[1].fighter.position.x = defender_x;
+        game.players[1].fighter.previous_position.x = defender_x;
+        game
+    }
+
+    // Player 1 up smashes while player 2 holds the given input, until the hit lands.
+    fn up_smash_player_2(game: &mut FightingGame, defender_input: &ControllerState) {
+        let mut input = ControllerState::default();
+        input.a_button.is_pressed = true;
+        while game.players[0].fighter.hit_targets.is_empty() {
+            assert!(game.match_frame < 60, "the up smash missed");
+            game.update(&[&input, defender_input]);
+        }
+    }
+
+    // Fox up smashes another Fox standing right in front of him. The hit only lands once,
+    // adds its damage to both players' totals, and the launch follows the knockback
+    // preview until the defender comes back down.
+    #[test]
+    fn up_smash_hits_once_and_launches_along_the_preview() {
+        let mut game = foxes_up_close();
+        let ground_y = game.players[1].fighter.position.y;
+
+        let mut input = ControllerState::default();
+        let idle_input = ControllerState::default();
+        input.a_button.is_pressed = true;
+        game.update(&[&input, &idle_input]);
+        assert_eq!(game.players[0].fighter.state, FighterState::Attack);
+        while game.players[1].fighter.state != FighterState::Hitstun {
+            assert!(game.players[0].fighter.state == FighterState::Attack, "the up smash missed");
+            game.update(&[&input, &idle_input]);
+        }
+
+        let up_smash = &game.players[0].fighter.attacks[1].hitboxes[0];
+        assert_eq!(game.players[1].fighter.percent, 18.0);
+        assert_eq!(game.players[0].damage_dealt, 18.0);
+        let knockback = knockback::calculate_knockback(up_smash, 0.0, game.players[1].fighter.attributes.weight);
+        let launch_velocity = knockback::calculate_launch_velocity(knockback, up_smash.angle, 1.0);
+        let trajectory = knockback::simulate_trajectory(
+            &game.players[1].fighter.position,
+            &launch_velocity,
+            game.players[1].fighter.attributes.gravity,
+            game.players[1].fighter.attributes.fall_velocity,
+            &game.stage.blast_zone,
+            300,
+        );
+        assert_eq!(game.players[1].fighter.hitstun_frames, trajectory.hitstun_frames);
+
+        let mut airborne_frames = 0;
+        for point in trajectory.points.iter().skip(1).take_while(|point| point.y > ground_y) {
+            airborne_frames += 1;
+            game.update(&[&idle_input, &idle_input]);
+            let position = &game.players[1].fighter.position;
+            assert!((position.x - point.x).abs() < 1e-9 && (position.y - point.y).abs() < 1e-9,
+                "at ({}, {}) instead of ({}, {})", position.x, position.y, point.x, point.y);
+        }
+        assert!(airborne_frames > 15, "only followed the launch for {} frames", airborne_frames);
+        assert_eq!(game.players[1].fighter.percent, 18.0);
+
+        game.change_match_state(MatchState::GameOver);
+        let results = game.results.as_ref().unwrap();
+        assert_eq!(results.players[0].damage_dealt, 18.0);
+        assert_eq!(results.players[1].damage_dealt, 0.0);
+    }
+
+    // An up smash at 200% sends the other Fox through the top of the blast zone. The KO
+    // goes to the Fox that hit them, and they come back on their own respawn point.
+    #[test]
+    fn ko_goes_to_the_last_hitter() {
+        let mut game = foxes_up_close();
+        game.players[1].fighter.percent = 200.0;
+
+        let mut input = ControllerState::default();
+        let idle_input = ControllerState::default();
+        input.a_button.is_pressed = true;
+        while game.players[1].falls == 0 {
+            assert!(game.match_frame < 300, "the up smash didn't KO");
+            game.update(&[&input, &idle_input]);
+        }
+
+        assert_eq!(game.players[0].kos, 1);
+        assert_eq!(game.players[1].kos, 0);
+        assert_eq!(game.players[1].last_hit_by, None);
+        let respawn_point = &game.stage.respawn_points[1];
+        let fighter = &game.players[1].fighter;
+        assert_eq!(fighter.state, FighterState::Respawn);
+        assert_eq!(fighter.percent, 0.0);
+        assert_eq!((fighter.position.x, fighter.position.y), (respawn_point.position.x, respawn_point.position.y));
+        assert_eq!(fighter.is_facing_right, respawn_point.is_facing_right);
+    }
+
+    // Shielding the up smash does no damage and causes shield stun for
+    // floor((18 + 4.45) / 2.235) = 10 frames after the hit.
+    #[test]
+    fn shielded_hits_cause_shield_stun() {
+        let mut game = foxes_up_close();
+        let mut shield_input = ControllerState::default();
+        shield_input.r_button.is_pressed = true;
+        up_smash_player_2(&mut game, &shield_input);
+
+        let defender = &game.players[1].fighter;
+        assert_eq!(defender.state, FighterState::ShieldStun);
+        assert_eq!(defender.percent, 0.0);
+        assert_eq!(game.players[0].damage_dealt, 0.0);
+        let mut shield_stun_frames = 0;
+        while game.players[1].fighter.state == FighterState::ShieldStun {
+            game.update(&[&ControllerState::default(), &shield_input]);
+            if game.players[1].fighter.state == FighterState::ShieldStun {
+                shield_stun_frames += 1;
+            }
+        }
+        assert_eq!(shield_stun_frames, 10);
+        assert_eq!(game.players[1].fighter.state, FighterState::Shield);
+    }
+
+    // The up smash ends 41 frames in and shield stun is 10 frames, so the advantage on
+    // shield is however far into the up smash it hit, minus 31.
+    #[test]
+    fn frame_advantage_on_shield() {
+        let mut game = foxes_up_close();
+        let mut shield_input = ControllerState::default();
+        shield_input.r_button.is_pressed = true;
+        up_smash_player_2(&mut game, &shield_input);
+        let hit_frame = game.players[0].fighter.state_frame as i64;
+        assert!(game.frame_advantage.is_none());
+
+        for _ in 0..60 {
+            game.update(&[&ControllerState::default(), &shield_input]);
+        }
+        let advantage = game.frame_advantage.as_ref().expect("no frame advantage after the hit");
+        assert_eq!((advantage.attacker, advantage.defender, advantage.on_shield), (0, 1, true));
+        assert_eq!(advantage.frames, hit_frame - 31);
+    }
+
+    // A missed tech keeps the defender down for 30 frames after landing, which is what
+    // decides the advantage rather than how much hitstun there was.
+    #[test]
+    fn frame_advantage_after_a_knock_down() {
+        let mut game = foxes_up_close();
+        let idle_input = ControllerState::default();
+        up_smash_player_2(&mut game, &idle_input);
+        let attacker_actionable_frame = game.match_frame + game.players[0].fighter.frames_until_actionable().unwrap();
+
+        while game.players[1].fighter.state != FighterState::KnockedDown {
+            assert!(game.match_frame < 200, "player 2 was never knocked down");
+            game.update(&[&idle_input, &idle_input]);
+        }
+        let defender_actionable_frame = game.match_frame + 30;
+        for _ in 0..60 {
+            game.update(&[&idle_input, &idle_input]);
+        }
+        let advantage = game.frame_advantage.as_ref().expect("no frame advantage after the hit");
+        assert!(!advantage.on_shield);
+        assert_eq!(advantage.frames, defender_actionable_frame as i64 - attacker_actionable_frame as i64);
+    }
+
+    // Crouching takes a third off of the knockback.
+    #[test]
+    fn crouching_reduces_knockback() {
+        let idle_input = ControllerState::default();
+        let mut standing_game = foxes_up_close();
+        up_smash_player_2(&mut standing_game, &idle_input);
+
+        let mut crouch_input = ControllerState::default();
+        crouch_input.y_axis.value = -0.65;
+        let mut crouching_game = foxes_up_close();
+        crouching_game.update(&[&idle_input, &crouch_input]);
+        assert_eq!(crouching_game.players[1].fighter.state, FighterState::Crouch);
+        up_smash_player_2(&mut crouching_game, &crouch_input);
+
+        let standing_speed = standing_game.players[1].fighter.knockback_velocity.magnitude();
+        let crouching_speed = crouching_game.players[1].fighter.knockback_velocity.magnitude();
+        assert_eq!(crouching_game.players[1].fighter.state, FighterState::Hitstun);
+        assert!((crouching_speed - standing_speed * 2.0 / 3.0).abs() < 1e-9, "launched at {} instead of {}", crouching_speed, standing_speed * 2.0 / 3.0);
+    }
+
+    // The up smash tumbles Fox even at 0%. Pressing L every other frame of hitstun techs
+    // in place on landing, and not pressing it gets him knocked down.
+    #[test]
+    fn tumble_landings_tech_or_knock_down() {
+        for should_tech in [true, false] {
+            let mut game = foxes_up_close();
+            up_smash_player_2(&mut game, &ControllerState::default());
+            assert!(game.players[1].fighter.hitstun_frames >= 32);
+
+            let idle_input = ControllerState::default();
+            let mut tech_input = ControllerState::default();
+            while game.players[1].fighter.state == FighterState::Hitstun {
+                tech_input.l_button.is_pressed = should_tech && game.players[1].fighter.state_frame.is_multiple_of(2);
+                game.update(&[&idle_input, &tech_input]);
+            }
+
+            let defender = &game.players[1].fighter;
+            if should_tech {
+                assert_eq!(defender.state, FighterState::Tech);
+                assert!(defender.is_invincible());
+            }
+            else {
+                assert_eq!(defender.state, FighterState::KnockedDown);
+                assert!(!defender.is_invincible());
+            }
+        }
+    }
+
+    // Player 1 keeps dashing off the stage until they are out of stocks. The stick is let go
+    // every so often since holding it walks up to the edge and teeters there. Nobody hit
+    // them, so neither fall is a KO.
+    #[test]
+    fn stock_match_results() {
+        let rules = MatchRules { stock_count: 2, time_limit_seconds: None, items: false };
+        let mut game = FightingGame::new(rules, load_stage("final_destination"), vec![Fighter::fox(), Fighter::fox()]);
+        game.change_match_state(MatchState::Playing);
+
+        let mut input = ControllerState::default();
+        let idle_input = ControllerState::default();
+        for frame in 0..2000 {
+            if game.match_state == MatchState::GameOver {
+                break;
+            }
+            assert!(game.results.is_none());
+            input.x_axis.value = if frame % 30 < 20 { 1.0 } else { 0.0 };
+            game.update(&[&input, &idle_input]);
+        }
+
+        let results = game.results.as_ref().expect("the match never ended");
+        assert_eq!(results.winner, Some(1));
+        assert!(!results.was_quit);
+        assert_eq!(results.frames_played, game.match_frame);
+        assert_eq!(results.players[0].stocks_remaining, 0);
+        assert_eq!(results.players[0].falls, 2);
+        assert_eq!(results.players[0].kos, 0);
+        assert_eq!(results.players[1].stocks_remaining, 2);
+        assert_eq!(results.players[1].falls, 0);
+        assert_eq!(results.players[1].kos, 0);
+    }
+
+    // Player 1 runs off the stage once and then stands still until time runs out.
+    #[test]
+    fn time_match_results() {
+        let rules = MatchRules { stock_count: 4, time_limit_seconds: Some(20), items: false };
+        let mut game = FightingGame::new(rules, load_stage("final_destination"), vec![Fighter::fox(), Fighter::fox()]);
+        game.change_match_state(MatchState::Playing);
+
+        let mut input = ControllerState::default();
+        input.x_axis.value = 1.0;
+        let idle_input = ControllerState::default();
+        while game.players[0].falls == 0 {
+            assert!(game.match_frame < 600, "player 1 never fell");
+            game.update(&[&input, &idle_input]);
+        }
+        while game.match_state != MatchState::GameOver {
+            game.update(&[&idle_input, &idle_input]);
+        }
+
+        let results = game.results.as_ref().unwrap();
+        assert_eq!(results.winner, Some(1));
+        assert_eq!(results.frames_played, 20 * 60);
+        assert_eq!(results.players[0].stocks_remaining, 3);
+        assert_eq!(results.players[0].falls, 1);
+        assert_eq!(results.players[1].stocks_remaining, 4);
+        assert_eq!(results.players[1].kos, 0);
+    }
+
+    // Holding jump through the apex of a full hop floats for as long as Peach can, without
+    // rising or falling, and then she falls normally. The 150 frames are from SmashWiki,
+    // Peach (SSBM).
+    #[test]
+    fn peach_float_holds_height() {
+        let mut game = FightingGame::new(MatchRules::default(), load_stage("final_destination"), vec![Fighter::peach()]);
+        game.change_match_state(MatchState::Playing);
+
+        let mut input = ControllerState::default();
+        input.x_button.is_pressed = true;
+        while game.players[0].fighter.state != FighterState::Float {
+            assert!(game.match_frame < 60, "Peach never floated");
+            game.update(&[&input]);
+        }
+
+        let float_height = game.players[0].fighter.position.y;
+        let mut float_frames = 0;
+        while game.players[0].fighter.state == FighterState::Float {
+            assert_eq!(game.players[0].fighter.position.y, float_height);
+            float_frames += 1;
+            game.update(&[&input]);
+        }
+        assert_eq!(float_frames, 150);
+        assert_eq!(game.players[0].fighter.state, FighterState::Airborne);
+        game.update(&[&input]);
+        assert!(game.players[0].fighter.position.y < float_height);
+    }
+
+    // A golden snapshot of this game's own air jump model, not Melee data. The 0.9 decay and
+    // the steady 12 frame rise in jigglypuff.json are stand-ins, so this only makes sure they
+    // don't change by accident. Each of the 5 air jumps covers the height a normal jump with
+    // that velocity would, so the heights decay by 0.81: 20, 16.2, 13.122, 10.6288 and 8.6093.
+    // A 6th jump does nothing.
+    #[test]
+    fn jigglypuff_air_jumps_golden() {
+        let mut game = FightingGame::new(MatchRules::default(), load_stage("final_destination"), vec![Fighter::jigglypuff()]);
+        game.change_match_state(MatchState::Playing);
+
+        let mut input = ControllerState::default();
+        input.x_button.is_pressed = true;
+        for _ in 0..10 {
+            game.update(&[&input]);
+        }
+        input.x_button.is_pressed = false;
+        for _ in 0..10 {
+            game.update(&[&input]);
+        }
+
+        let mut expected_height = 20.0;
+        for air_jump in 1..=5 {
+            let start_height = game.players[0].fighter.position.y;
+            input.x_button.is_pressed = true;
+            game.update(&[&input]);
+            input.x_button.is_pressed = false;
+            for _ in 1..12 {
+                game.update(&[&input]);
+            }
+
+            let fighter = &game.players[0].fighter;
+            assert_eq!(fighter.air_jumps_left, 5 - air_jump);
+            let height = fighter.position.y - start_height;
+            assert!((height - expected_height).abs() < 1e-9, "air jump {} rose {}", air_jump, height);
+            expected_height *= 0.81;
+            for _ in 0..3 {
+                game.update(&[&input]);
+            }
+        }
+
+        let previous_velocity_y = game.players[0].fighter.velocity.y;
+        input.x_button.is_pressed = true;
+        game.update(&[&input]);
+        assert_eq!(game.players[0].fighter.air_jumps_left, 0);
+        assert_eq!(game.players[0].fighter.air_jump_rise_frames_left, 0);
+        assert!(game.players[0].fighter.velocity.y < previous_velocity_y);
+    }
+
+    // A small xorshift generator, so every run presses the same buttons.
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn chance(&mut self, one_in: u64) -> bool {
+            self.next().is_multiple_of(one_in)
+        }
+
+        fn axis(&mut self) -> f64 {
+            (self.next() % 2001) as f64 / 1000.0 - 1.0
+        }
+    }
+
+    // Inputs are held for a while before changing, like a player mashing would, so
+    // that dashes, runs, jumps and air dodges all get a chance to play out.
+    fn randomize_input(random: &mut Random, input: &mut ControllerState) {
+        if random.chance(6) {
+            input.x_axis.value = if random.chance(2) { random.axis().signum() } else { random.axis() };
+        }
+        if random.chance(6) {
+            input.y_axis.value = if random.chance(2) { random.axis().signum() } else { random.axis() };
+        }
+        if random.chance(10) {
+            input.x_axis.value = 0.0;
+            input.y_axis.value = 0.0;
+        }
+        input.x_button.is_pressed = random.chance(12);
+        input.a_button.is_pressed = random.chance(20);
+        input.r_button.is_pressed = random.chance(8);
+        input.l_button.is_pressed = random.chance(40);
+    }
+
+    // How high a ground line is at x, if x is over it at all.
+    fn ground_height_at(line: &CollisionLine, x: f64) -> Option<f64> {
+        let left = line.segment.left_point();
+        let right = line.segment.right_point();
+        if x < left.x || x > right.x || right.x - left.x <= 0.0 {
+            return None;
+        }
+        Some(left.y + (right.y - left.y) * (x - left.x) / (right.x - left.x))
+    }
+
+    // Mashes random inputs with every character on every stage and makes sure nothing ever
+    // pushes the bottom of the ECB through solid ground from above, including while the
+    // stage is moving.
+    #[test]
+    fn random_inputs_never_go_below_solid_ground() {
+        let tolerance = 0.01;
+        let characters: [fn() -> Fighter; 7] = [
+            Fighter::fox,
+            Fighter::falco,
+            Fighter::marth,
+            Fighter::sheik,
+            Fighter::peach,
+            Fighter::jigglypuff,
+            Fighter::captain_falcon,
+        ];
+        for stage_name in ["final_destination", "battlefield", "v_stage", "yoshis_story"] {
+            for seed in 1..=14u64 {
+                let mut random = Random(0x9e37_79b9_7f4a_7c15 ^ seed);
+                let fighter = characters[seed as usize % characters.len()]();
+                let rules = MatchRules { stock_count: 1000, time_limit_seconds: None, items: false };
+                let mut game = FightingGame::new(rules, load_stage(stage_name), vec![fighter]);
+                game.change_match_state(MatchState::Playing);
+
+                let mut input = ControllerState::default();
+                for frame in 0..3000 {
+                    randomize_input(&mut random, &mut input);
+
+                    let previous_lines = game.stage.collision_poly_lines.clone();
+                    let previous_fighter = game.players[0].fighter.clone();
+                    let previous_falls = game.players[0].falls;
+                    game.update(&[&input]);
+
+                    let player = &game.players[0];
+                    if player.falls != previous_falls || player.fighter.state == FighterState::Respawn {
+                        continue;
+                    }
+                    let previous_bottom_x = previous_fighter.position.x + previous_fighter.ecb.bottom.x;
+                    let previous_bottom_y = previous_fighter.position.y + previous_fighter.ecb.bottom.y;
+                    let bottom_x = player.fighter.position.x + player.fighter.ecb.bottom.x;
+                    let bottom_y = player.fighter.position.y + player.fighter.ecb.bottom.y;
+
+                    let poly_lines = previous_lines.iter().zip(game.stage.collision_poly_lines.iter());
+                    for (previous_poly_line, poly_line) in poly_lines {
+                        for (previous_line, line) in previous_poly_line.lines.iter().zip(poly_line.lines.iter()) {
+                            if line.is_pass_through || !line.is_ground() {
+                                continue;
+                            }
+                            let was_above = ground_height_at(previous_line, previous_bottom_x)
+                                .is_some_and(|ground_y| previous_bottom_y >= ground_y - tolerance);
+                            let is_below = ground_height_at(line, bottom_x)
+                                .is_some_and(|ground_y| bottom_y < ground_y - tolerance);
+                            assert!(
+                                !(was_above && is_below),
+                                "{} {} seed {} frame {}: went from ({}, {}) in {:?} to ({}, {}) in {:?}, below the ground from ({}, {}) to ({}, {})",
+                                player.fighter.name, stage_name, seed, frame,
+                                previous_bottom_x, previous_bottom_y, previous_fighter.state,
+                                bottom_x, bottom_y, player.fighter.state,
+                                line.segment.point_a.x, line.segment.point_a.y,
+                                line.segment.point_b.x, line.segment.point_b.y,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Fox starts a full hop under Battlefield's left platform at y 16, rises up through it
+    // and then lands on it from above.
+    #[test]
+    fn platforms_are_passed_from_below_and_landed_on_from_above() {
+        let mut game = FightingGame::new(MatchRules::default(), load_stage("battlefield"), vec![Fighter::fox()]);
+        game.change_match_state(MatchState::Playing);
+        let start = Point { x: -22.0, y: 0.0 };
+        game.players[0].fighter.position = start.clone();
+        game.players[0].fighter.previous_position = start;
+
+        let mut input = ControllerState::default();
+        input.x_button.is_pressed = true;
+        let mut has_risen_through = false;
+        for _ in 0..60 {
+            game.update(&[&input]);
+            let fighter = &game.players[0].fighter;
+            has_risen_through |= fighter.is_airborne() && fighter.velocity.y > 0.0 && fighter.position.y > 16.0;
+            if has_risen_through && !fighter.is_airborne() {
+                break;
+            }
+        }
+        let fighter = &game.players[0].fighter;
+        assert!(has_risen_through, "never rose through the platform");
+        assert!(!fighter.is_airborne(), "never landed, in {:?} at y {}", fighter.state, fighter.position.y);
+        assert!((fighter.position.y - 16.0).abs() < 1e-9, "landed at y {}", fighter.position.y);
+        assert!(fighter.is_on_platform);
+    }
+
+    // Player 1 spawns on Battlefield's left platform. Easing the stick down crouches on
+    // it, while tapping it down from neutral falls through.
+    #[test]
+    fn platforms_are_fallen_through_on_a_down_tap_but_not_a_held_down() {
+        let mut game = FightingGame::new(MatchRules::default(), load_stage("battlefield"), vec![Fighter::fox()]);
+        game.change_match_state(MatchState::Playing);
+        let idle_input = ControllerState::default();
+        game.update(&[&idle_input]);
+        assert_eq!(game.players[0].fighter.position.y, 16.0);
+
+        let mut input = ControllerState::default();
+        input.y_axis.value = -0.3;
+        for _ in 0..5 {
+            game.update(&[&input]);
+        }
+        input.y_axis.value = -1.0;
+        for _ in 0..30 {
+            game.update(&[&input]);
+            assert_eq!(game.players[0].fighter.state, FighterState::Crouch);
+            assert_eq!(game.players[0].fighter.position.y, 16.0);
+        }
+
+        for _ in 0..5 {
+            game.update(&[&idle_input]);
+        }
+        game.update(&[&input]);
+        assert_eq!(game.players[0].fighter.state, FighterState::FallThrough);
+        for _ in 0..10 {
+            game.update(&[&input]);
+        }
+        assert!(game.players[0].fighter.position.y < 16.0);
+    }
+
+    // Fox walks left from the middle of V Stage, down one slope and up the steeper one past
+    // it, then turns around and walks back. He never leaves the ground, stays on the line
+    // he is over, and his ground angle is always the angle of that line.
+    #[test]
+    fn walking_over_slopes_follows_the_ground() {
+        let mut game = FightingGame::new(MatchRules::default(), load_stage("v_stage"), vec![Fighter::fox()]);
+        game.change_match_state(MatchState::Playing);
+        let ground_lines: Vec<LineSegment> = game.stage.collision_poly_lines[0].lines.iter()
+            .map(|line| line.segment.clone())
+            .collect();
+
+        let mut input = ControllerState::default();
+        let mut has_walked_uphill = false;
+        let mut has_walked_downhill = false;
+        for (stick_x, frames) in [(-0.5, 40), (0.0, 10), (0.5, 40)] {
+            input.x_axis.value = stick_x;
+            for frame in 0..frames {
+                let previous_y = game.players[0].fighter.position.y;
+                game.update(&[&input]);
+                let fighter = &game.players[0].fighter;
+                assert!(!fighter.is_airborne(), "left the ground in {:?} at ({}, {}) walking {} on frame {}", fighter.state, fighter.position.x, fighter.position.y, stick_x, frame);
+
+                // Right at a corner either line's angle could be the right one.
+                let position = &fighter.position;
+                let possible_line = ground_lines.iter()
+                    .find(|segment| position.x > segment.left_point().x + 0.5 && position.x < segment.right_point().x - 0.5);
+                if let Some(segment) = possible_line {
+                    let line_y = segment.point_a.y + (position.x - segment.point_a.x) * segment.slope();
+                    assert!((position.y - line_y).abs() < 1e-6, "at ({}, {}) but the ground is at y {}", position.x, position.y, line_y);
+                    assert!((fighter.ground_angle - segment.direction().angle()).abs() < 1e-9, "ground angle {} on a line at {}", fighter.ground_angle, segment.direction().angle());
+                }
+                has_walked_uphill |= fighter.state == FighterState::Walk && position.y > previous_y;
+                has_walked_downhill |= fighter.state == FighterState::Walk && position.y < previous_y;
+            }
+        }
+        assert!(has_walked_uphill && has_walked_downhill);
+    }
+
+    // Fox on Battlefield's main stage, a bit left of the right edge at x 39.
+    fn fox_near_battlefields_edge() -> FightingGame {
+        let mut game = FightingGame::new(MatchRules::default(), load_stage("battlefield"), vec![Fighter::fox()]);
+        game.change_match_state(MatchState::Playing);
+        let start = Point { x: 25.0, y: 0.0 };
+        game.players[0].fighter.position = start.clone();
+        game.players[0].fighter.previous_position = start;
+        game
+    }
+
+    // Walking slowly stops at the edge and teeters there, without going over.
+    #[test]
+    fn walking_slowly_to_an_edge_teeters() {
+        let mut game = fox_near_battlefields_edge();
+        let mut input = ControllerState::default();
+        input.x_axis.value = 0.35;
+        for _ in 0..120 {
+            game.update(&[&input]);
+            if game.players[0].fighter.state == FighterState::Teeter {
+                break;
+            }
+        }
+        let fighter = &game.players[0].fighter;
+        assert_eq!(fighter.state, FighterState::Teeter);
+        assert!(fighter.position.x <= 39.0 && fighter.position.y == 0.0, "teetering at ({}, {})", fighter.position.x, fighter.position.y);
+
+        for _ in 0..30 {
+            game.update(&[&input]);
+            assert_eq!(game.players[0].fighter.state, FighterState::Teeter);
+        }
+    }
+
+    // Dashing at the edge carries on off of it into the air.
+    #[test]
+    fn dashing_off_an_edge_goes_airborne() {
+        let mut game = fox_near_battlefields_edge();
+        let mut input = ControllerState::default();
+        input.x_axis.value = 1.0;
+        for _ in 0..30 {
+            game.update(&[&input]);
+            if game.players[0].fighter.is_airborne() {
+                break;
+            }
+        }
+        let fighter = &game.players[0].fighter;
+        assert_eq!(fighter.state, FighterState::Airborne);
+        assert!(fighter.position.x > 39.0, "went airborne at x {}", fighter.position.x);
+    }
+
+    // Fox drops onto the top platform on Yoshi's Story, which goes back and forth 24 units
+    // every 240 frames, and stands there through two turnarounds. He has to keep his place
+    // on the platform the whole time.
+    #[test]
+    fn moving_platforms_carry_fighters_through_turnarounds() {
+        let mut game = FightingGame::new(MatchRules::default(), load_stage("yoshis_story"), vec![Fighter::fox()]);
+        game.change_match_state(MatchState::Playing);
+        let platform_index = 3;
+        assert!(game.stage.collision_poly_lines[platform_index].path.is_some());
+        let start = Point { x: game.stage.collision_poly_lines[platform_index].offset.x, y: 40.0 };
+        game.players[0].fighter.position = start.clone();
+        game.players[0].fighter.previous_position = start;
+
+        // Landing slides a little, so this waits until Fox has settled.
+        let input = ControllerState::default();
+        loop {
+            assert!(game.match_frame < 60, "never settled on the platform");
+            game.update(&[&input]);
+            let fighter = &game.players[0].fighter;
+            if fighter.state == FighterState::Idle && fighter.velocity.x == 0.0 && (fighter.position.y - 34.0).abs() < 1e-9 {
+                break;
+            }
+        }
+        let place_on_platform = game.players[0].fighter.position.x - game.stage.collision_poly_lines[platform_index].offset.x;
+
+        let mut has_moved_both_ways = (false, false);
+        for _ in 0..600 {
+            let previous_x = game.players[0].fighter.position.x;
+            game.update(&[&input]);
+            let fighter = &game.players[0].fighter;
+            let platform = &game.stage.collision_poly_lines[platform_index];
+            assert!(!fighter.is_airborne(), "fell off in {:?} on stage frame {}", fighter.state, game.stage.frame);
+            assert!((fighter.position.y - 34.0).abs() < 1e-9, "at y {} on stage frame {}", fighter.position.y, game.stage.frame);
+            assert!((fighter.position.x - platform.offset.x - place_on_platform).abs() < 1e-9, "slid on the platform on stage frame {}", game.stage.frame);
+            has_moved_both_ways.0 |= fighter.position.x > previous_x;
+            has_moved_both_ways.1 |= fighter.position.x < previous_x;
+        }
+        assert_eq!(has_moved_both_ways, (true, true));
+    }
+
+    // Where each point of the fighter's ECB is in the stage.
+    fn ecb_points(fighter: &Fighter) -> [Point; 4] {
+        [&fighter.ecb.bottom, &fighter.ecb.left, &fighter.ecb.top, &fighter.ecb.right]
+            .map(|point| Point { x: fighter.position.x + point.x, y: fighter.position.y + point.y })
+    }
+
+    // Whether the two segments cross each other, not counting only touching.
+    fn segments_cross(a1: &Point, a2: &Point, b1: &Point, b2: &Point) -> bool {
+        let side = |p1: &Point, p2: &Point, p: &Point| (p2.x - p1.x) * (p.y - p1.y) - (p2.y - p1.y) * (p.x - p1.x);
+        side(a1, a2, b1) * side(a1, a2, b2) < 0.0 && side(b1, b2, a1) * side(b1, b2, a2) < 0.0
+    }
+
+    fn distance_to_segment(point: &Point, segment: &LineSegment) -> f64 {
+        let Point { x: x1, y: y1 } = segment.point_a;
+        let Point { x: x2, y: y2 } = segment.point_b;
+        let length_squared = (x2 - x1).powi(2) + (y2 - y1).powi(2);
+        let along = (((point.x - x1) * (x2 - x1) + (point.y - y1) * (y2 - y1)) / length_squared).clamp(0.0, 1.0);
+        ((point.x - x1 - along * (x2 - x1)).powi(2) + (point.y - y1 - along * (y2 - y1)).powi(2)).sqrt()
+    }
+
+    // Launches Fox far faster than his ECB is wide into Final Destination's walls and
+    // underside, from beside and below the stage. No ECB point may ever cross one of those
+    // lines, and each launch has to actually reach the stage.
+    #[test]
+    fn high_speed_launches_never_cross_walls_or_the_underside() {
+        let launches = [
+            (Point { x: -80.0, y: -30.0 }, Vector { x: 30.0, y: 0.0 }),
+            (Point { x: 80.0, y: -30.0 }, Vector { x: -30.0, y: 0.0 }),
+            (Point { x: 0.0, y: -125.0 }, Vector { x: 0.0, y: 30.0 }),
+            (Point { x: -75.0, y: -125.0 }, Vector { x: 25.0, y: 25.0 }),
+            (Point { x: 75.0, y: -125.0 }, Vector { x: -25.0, y: 25.0 }),
+        ];
+        for (start, launch) in launches {
+            let mut game = FightingGame::new(MatchRules::default(), load_stage("final_destination"), vec![Fighter::fox()]);
+            game.change_match_state(MatchState::Playing);
+            game.players[0].fighter.position = start.clone();
+            game.players[0].fighter.previous_position = start.clone();
+            game.players[0].fighter.take_hit(0.0, &launch, 40);
+            let lines: Vec<LineSegment> = game.stage.collision_poly_lines.iter()
+                .flat_map(|poly_line| &poly_line.lines)
+                .filter(|line| !line.is_pass_through && !line.is_ground())
+                .map(|line| line.segment.clone())
+                .collect();
+
+            let mut has_reached_the_stage = false;
+            let input = ControllerState::default();
+            for frame in 0..10 {
+                let previous_points = ecb_points(&game.players[0].fighter);
+                game.update(&[&input]);
+                assert_eq!(game.players[0].falls, 0, "launched from ({}, {}) at ({}, {}), frame {}: fell", start.x, start.y, launch.x, launch.y, frame);
+                let points = ecb_points(&game.players[0].fighter);
+                for (previous_point, point) in previous_points.iter().zip(&points) {
+                    for line in &lines {
+                        assert!(
+                            !segments_cross(previous_point, point, &line.point_a, &line.point_b),
+                            "launched from ({}, {}) at ({}, {}), frame {}: an ECB point went from ({}, {}) to ({}, {}) through ({}, {}) to ({}, {})",
+                            start.x, start.y, launch.x, launch.y, frame,
+                            previous_point.x, previous_point.y, point.x, point.y,
+                            line.point_a.x, line.point_a.y, line.point_b.x, line.point_b.y,
+                        );
+                        has_reached_the_stage |= distance_to_segment(point, line) < 0.5;
+                    }
+                }
+            }
+            assert!(has_reached_the_stage, "launched from ({}, {}) at ({}, {}) but never reached the stage", start.x, start.y, launch.x, launch.y);
         }
     }
 }
@@ -1,235 +1,831 @@
 use piston_window::*;
 
 use crate::general_math;
-use crate::line_math::PolyLine;
-use crate::fighter::Fighter;
-use crate::fighting_game::FightingGame;
+use crate::point_math::Point;
+use crate::collision_line::CollisionPolyLine;
+use crate::fighter::{Fighter, FighterState};
+use crate::ecb::ECB;
+use crate::controller_state::ControllerState;
+use crate::fighting_game::{FightingGame, MatchState};
+use crate::match_rules::MatchResults;
+use crate::stage::{Bounds, LedgeSide};
+use crate::training_mode::{TrainingMode, DIBehavior};
+use crate::rewind::Rewind;
+use crate::input_history::InputHistory;
+use crate::canvas::{Canvas, Viewport, ViewportCanvas};
+use crate::camera::{Camera, CameraMode};
+
+// The size of the screen everything is laid out on, before it's scaled to the window.
+pub const VIRTUAL_WIDTH: f64 = 800.0;
+pub const VIRTUAL_HEIGHT: f64 = 600.0;
 
 pub struct FightingGameRenderer {
-    pub camera_zoom: f64,
-    pub camera_x: f64,
-    pub camera_y: f64,
+    pub camera: Camera,
     pub middle_mouse_is_down: bool,
-    pub glyphs: Glyphs,
+    pub input_history: InputHistory,
+    pub show_input_history: bool,
+    pub debug_layers: DebugLayers,
+}
+
+// Everything a frame is drawn from besides the renderer's own state.
+pub struct RenderContext<'a> {
+    pub game: &'a FightingGame,
+    pub possible_reload_error: &'a Option<String>,
+    pub possible_training_mode: Option<&'a TrainingMode>,
+    pub rewind: &'a Rewind,
+    pub window_width: f64,
+    pub window_height: f64,
+    pub interpolation: f64,
+}
+
+// Extra things to draw for debugging, each toggled with a function key.
+pub struct DebugLayers {
+    pub hurtboxes: bool,
+    pub hitboxes: bool,
+    pub ecb: bool,
+    pub vectors: bool,
+    pub normals: bool,
+}
+
+impl DebugLayers {
+    pub fn default() -> Self {
+        Self {
+            hurtboxes: false,
+            hitboxes: false,
+            ecb: false,
+            vectors: false,
+            normals: false,
+        }
+    }
+}
+
+// Which part of the virtual screen a piece of the HUD is placed from.
+#[derive(Copy, Clone)]
+enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+    Center,
+}
+
+impl Anchor {
+    fn position(self, offset_x: f64, offset_y: f64) -> [f64; 2] {
+        let (x, y) = match self {
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::Top => (0.5 * VIRTUAL_WIDTH, 0.0),
+            Anchor::TopRight => (VIRTUAL_WIDTH, 0.0),
+            Anchor::BottomLeft => (0.0, VIRTUAL_HEIGHT),
+            Anchor::Bottom => (0.5 * VIRTUAL_WIDTH, VIRTUAL_HEIGHT),
+            Anchor::BottomRight => (VIRTUAL_WIDTH, VIRTUAL_HEIGHT),
+            Anchor::Center => (0.5 * VIRTUAL_WIDTH, 0.5 * VIRTUAL_HEIGHT),
+        };
+        [x + offset_x, y + offset_y]
+    }
 }
 
 impl FightingGameRenderer {
-    pub fn to_piston_window(window: &mut PistonWindow) -> FightingGameRenderer {
-        FightingGameRenderer {
-            camera_zoom: 4.0,
-            camera_x: 0.0,
-            camera_y: 20.0,
+    pub fn default() -> Self {
+        Self {
+            camera: Camera::default(),
             middle_mouse_is_down: false,
-            glyphs: window.load_font("C:/Windows/Fonts/consola.ttf").unwrap(),
+            input_history: InputHistory::default(),
+            show_input_history: false,
+            debug_layers: DebugLayers::default(),
         }
     }
 
-    pub fn handle_mouse_pan_and_zoom(&mut self, event: &Event) {
+    // I toggles the input history panel, C toggles the camera between following the fighters
+    // and free, and F1 through F5 toggle the debug layers.
+    pub fn handle_key_toggles(&mut self, event: &Event) {
+        if let Some(args) = event.button_args() {
+            if args.state == ButtonState::Press {
+                match args.button {
+                    Button::Keyboard(Key::I) => self.show_input_history = !self.show_input_history,
+                    Button::Keyboard(Key::C) => self.camera.toggle_mode(),
+                    Button::Keyboard(Key::F1) => self.debug_layers.hurtboxes = !self.debug_layers.hurtboxes,
+                    Button::Keyboard(Key::F2) => self.debug_layers.hitboxes = !self.debug_layers.hitboxes,
+                    Button::Keyboard(Key::F3) => self.debug_layers.ecb = !self.debug_layers.ecb,
+                    Button::Keyboard(Key::F4) => self.debug_layers.vectors = !self.debug_layers.vectors,
+                    Button::Keyboard(Key::F5) => self.debug_layers.normals = !self.debug_layers.normals,
+                    _ => ()
+                }
+            }
+        }
+    }
+
+    // Moving the camera by hand frees it from following the fighters. Mouse movement is in
+    // window pixels, so the window size is needed to know how far that is on the stage.
+    pub fn handle_mouse_pan_and_zoom(&mut self, event: &Event, window_width: f64, window_height: f64) {
         if let Some(args) = event.mouse_scroll_args() {
-            let scroll_direction = args[1];
-            self.camera_zoom += scroll_direction;
-            self.camera_zoom = self.camera_zoom.max(1.0);
+            self.camera.mode = CameraMode::Free;
+            self.camera.zoom(args[1]);
         }
 
         if let Some(args) = event.button_args() {
-            match args.state {
-                ButtonState::Press => match args.button {
-                    Button::Mouse(button) => match button {
-                        MouseButton::Middle => self.middle_mouse_is_down = true,
-                        _ => ()
-                    },
-                    _ => ()
-                },
-                ButtonState::Release => match args.button {
-                    Button::Mouse(button) => match button {
-                        MouseButton::Middle => self.middle_mouse_is_down = false,
-                        _ => ()
-                    },
-                    _ => ()
-                }
+            if args.button == Button::Mouse(MouseButton::Middle) {
+                self.middle_mouse_is_down = args.state == ButtonState::Press;
             }
         }
 
         if let Some(args) = event.mouse_relative_args() {
             if self.middle_mouse_is_down {
-                self.camera_x += args[0] / self.camera_zoom;
-                self.camera_y += args[1] / self.camera_zoom;
+                // The stage follows the mouse, and stage y goes up while window y goes down.
+                let viewport = Viewport::fit(VIRTUAL_WIDTH, VIRTUAL_HEIGHT, window_width, window_height);
+                let window_pixels_per_unit = viewport.scale * self.pixels_per_unit();
+                self.camera.mode = CameraMode::Free;
+                self.camera.x -= args[0] / window_pixels_per_unit;
+                self.camera.y += args[1] / window_pixels_per_unit;
             }
         }
     }
 
-    fn game_x_to_screen_x(&self, value: f64, window_width: f64) -> f64 {
-        value * self.camera_zoom + 0.5 * window_width + self.camera_x * self.camera_zoom
+    // Should be called once per game update.
+    pub fn update_camera(&mut self, game: &FightingGame) {
+        self.camera.update(game, VIRTUAL_WIDTH / VIRTUAL_HEIGHT);
     }
-    fn game_y_to_screen_y(&self, value: f64, window_height: f64) -> f64 {
-        -value * self.camera_zoom + 0.5 * window_height + self.camera_y * self.camera_zoom
+
+    // How many pixels on the virtual screen one stage unit takes up.
+    fn pixels_per_unit(&self) -> f64 {
+        VIRTUAL_HEIGHT / self.camera.view_height
     }
 
-    pub fn render(
-        &mut self,
-        context: Context,
-        graphics: &mut G2d,
-        device: &mut gfx_device_gl::Device,
-        game: &FightingGame,
-        window_width: f64,
-        window_height: f64,
-        interpolation: f64
+    fn game_x_to_screen_x(&self, value: f64) -> f64 {
+        (value - self.camera.x) * self.pixels_per_unit() + 0.5 * VIRTUAL_WIDTH
+    }
+    fn game_y_to_screen_y(&self, value: f64) -> f64 {
+        -(value - self.camera.y) * self.pixels_per_unit() + 0.5 * VIRTUAL_HEIGHT
+    }
+
+    fn draw_game_line(
+        &self,
+        canvas: &mut impl Canvas,
+        color: [f32; 4],
+        start: &Point,
+        end: &Point,
     ) {
-        let interpolation = if game.is_paused { 1.0 } else { interpolation };
-        clear([0.0, 0.0, 0.0, 1.0], graphics);
-        self.draw_collision_lines(context, graphics, &game.collision_poly_lines, window_width, window_height);
-        self.draw_character(context, graphics, &game.player, window_width, window_height, interpolation);
-        self.draw_debug_text(context, graphics, device, &game, window_width, window_height);
+        canvas.draw_line(
+            color,
+            0.5,
+            [
+                self.game_x_to_screen_x(start.x),
+                self.game_y_to_screen_y(start.y),
+                self.game_x_to_screen_x(end.x),
+                self.game_y_to_screen_y(end.y),
+            ],
+        );
+    }
+
+    fn draw_game_circle(
+        &self,
+        canvas: &mut impl Canvas,
+        color: [f32; 4],
+        center: &Point,
+        radius: f64,
+        is_filled: bool,
+    ) {
+        canvas.draw_circle(
+            color,
+            [self.game_x_to_screen_x(center.x), self.game_y_to_screen_y(center.y)],
+            radius * self.pixels_per_unit(),
+            is_filled,
+        );
+    }
+
+    // Everything is drawn on the virtual screen, which is scaled to fit the window and
+    // letterboxed if the window is a different shape.
+    pub fn render<C: Canvas>(&self, canvas: &mut C, context: &RenderContext) {
+        let game = context.game;
+        let interpolation = if game.match_state == MatchState::Playing { context.interpolation } else { 1.0 };
+        let background_color = [0.0, 0.0, 0.0, 1.0];
+        let viewport = Viewport::fit(VIRTUAL_WIDTH, VIRTUAL_HEIGHT, context.window_width, context.window_height);
+        canvas.clear(background_color);
+
+        let mut canvas = ViewportCanvas { canvas, viewport };
+        self.draw_collision_lines(&mut canvas, &game.stage.collision_poly_lines);
+        self.draw_blast_zone(&mut canvas, &game.stage.blast_zone);
+        for player in &game.players {
+            if player.stocks > 0 {
+                self.draw_character(&mut canvas, &player.fighter, interpolation);
+            }
+        }
+        self.draw_debug_layers(&mut canvas, game);
+        if let Some(training_mode) = context.possible_training_mode {
+            self.draw_knockback_previews(&mut canvas, game, training_mode);
+        }
+        viewport.draw_bars(&mut *canvas.canvas, background_color);
+        self.draw_debug_text(&mut canvas, game, context.possible_reload_error, context.possible_training_mode, context.rewind);
     }
 
     fn draw_character(
         &self,
-        context: Context,
-        graphics: &mut G2d,
+        canvas: &mut impl Canvas,
         character: &Fighter,
-        window_width: f64,
-        window_height: f64,
         interpolation: f64,
     ) {
         // Draw the main character body.
         let character_interpolated_x = general_math::lerp(character.previous_position.x, character.position.x, interpolation);
         let character_interpolated_y = general_math::lerp(character.previous_position.y, character.position.y, interpolation);
-        let character_pixel_x = self.game_x_to_screen_x(character_interpolated_x, window_width);
-        let character_pixel_y = self.game_y_to_screen_y(character_interpolated_y, window_height);
+        let character_pixel_x = self.game_x_to_screen_x(character_interpolated_x);
+        let character_pixel_y = self.game_y_to_screen_y(character_interpolated_y);
+        let ecb = character.previous_ecb.lerp(&character.ecb, interpolation);
+
+        // Draw the respawn platform under the character while they are on it.
+        if character.state == FighterState::Respawn {
+            let platform_half_width = 6.0 * self.pixels_per_unit();
+            canvas.draw_line(
+                [0.9, 0.9, 0.3, 1.0],
+                1.0,
+                [
+                    character_pixel_x - platform_half_width,
+                    character_pixel_y,
+                    character_pixel_x + platform_half_width,
+                    character_pixel_y,
+                ],
+            );
+        }
+
+        let body_color = if character.is_invincible() { [0.9, 0.9, 0.9, 1.0] } else { [0.5, 0.5, 0.5, 1.0] };
         let screen_ecb = [
-            [self.camera_zoom * (character.ecb.bottom.x), self.camera_zoom * -(character.ecb.bottom.y)],
-            [self.camera_zoom * (character.ecb.left.x), self.camera_zoom * -(character.ecb.left.y)],
-            [self.camera_zoom * (character.ecb.top.x), self.camera_zoom * -(character.ecb.top.y)],
-            [self.camera_zoom * (character.ecb.right.x), self.camera_zoom * -(character.ecb.right.y)]
+            [character_pixel_x + self.pixels_per_unit() * (ecb.bottom.x), character_pixel_y + self.pixels_per_unit() * -(ecb.bottom.y)],
+            [character_pixel_x + self.pixels_per_unit() * (ecb.left.x), character_pixel_y + self.pixels_per_unit() * -(ecb.left.y)],
+            [character_pixel_x + self.pixels_per_unit() * (ecb.top.x), character_pixel_y + self.pixels_per_unit() * -(ecb.top.y)],
+            [character_pixel_x + self.pixels_per_unit() * (ecb.right.x), character_pixel_y + self.pixels_per_unit() * -(ecb.right.y)]
         ];
-        polygon(
-            [0.5, 0.5, 0.5, 1.0],
-            &screen_ecb,
-            context.transform.trans(character_pixel_x, character_pixel_y),
-            graphics,
-        );
+        canvas.draw_polygon(body_color, &screen_ecb);
 
         // Draw a way to tell which way the character is facing.
-        let character_facing_width = 2.0 * self.camera_zoom;
+        let character_facing_width = 2.0 * self.pixels_per_unit();
         let character_facing_offset = (character_facing_width + 6.0) * character.facing_direction();
-        rectangle(
+        canvas.draw_rectangle(
             [0.9, 0.9, 0.9, 1.0],
-            rectangle::rectangle_by_corners(0.0, 0.0, character_facing_width, character_facing_width),
-            context.transform.trans(character_pixel_x + character_facing_offset - 0.5 * character_facing_width, character_pixel_y - 12.0 * self.camera_zoom),
-            graphics,
+            [
+                character_pixel_x + character_facing_offset - 0.5 * character_facing_width,
+                character_pixel_y - ecb.top.y * self.pixels_per_unit(),
+                character_facing_width,
+                character_facing_width,
+            ],
         );
     }
 
+    // Debug layers are drawn where things actually are rather than interpolated.
+    fn draw_debug_layers(
+        &self,
+        canvas: &mut impl Canvas,
+        game: &FightingGame,
+    ) {
+        if self.debug_layers.normals {
+            let normal_length = 4.0;
+            for poly_line in &game.stage.collision_poly_lines {
+                for collision_line in &poly_line.lines {
+                    let segment = &collision_line.segment;
+                    let middle = segment.point_a.lerp(&segment.point_b, 0.5);
+                    let normal = segment.normal();
+                    let normal_end = Point {
+                        x: middle.x + normal_length * normal.x,
+                        y: middle.y + normal_length * normal.y,
+                    };
+                    self.draw_game_line(canvas, [0.3, 0.6, 0.9, 1.0], &middle, &normal_end);
+                }
+            }
+
+            // Ledges stick out past the end of the ground they hang off.
+            let ledge_length = 3.0;
+            for ledge in &game.stage.ledges {
+                let outward = if ledge.side == LedgeSide::Left { -1.0 } else { 1.0 };
+                let ledge_end = Point {
+                    x: ledge.position.x + outward * ledge_length,
+                    y: ledge.position.y,
+                };
+                self.draw_game_line(canvas, [0.9, 0.6, 0.2, 1.0], &ledge.position, &ledge_end);
+            }
+        }
+
+        for player in &game.players {
+            if player.stocks == 0 {
+                continue;
+            }
+            let fighter = &player.fighter;
+
+            if self.debug_layers.hurtboxes {
+                let hurtbox = fighter.hurtbox();
+                let color = [0.9, 0.9, 0.2, 0.8];
+                self.draw_game_circle(canvas, color, &hurtbox.bottom, hurtbox.radius, false);
+                self.draw_game_circle(canvas, color, &hurtbox.top, hurtbox.radius, false);
+                for side in &[-1.0, 1.0] {
+                    let bottom = Point { x: hurtbox.bottom.x + side * hurtbox.radius, y: hurtbox.bottom.y };
+                    let top = Point { x: hurtbox.top.x + side * hurtbox.radius, y: hurtbox.top.y };
+                    self.draw_game_line(canvas, color, &bottom, &top);
+                }
+            }
+
+            // Colored by hitbox id like Melee's debug display.
+            if self.debug_layers.hitboxes {
+                let hitbox_colors = [
+                    [0.9, 0.2, 0.2, 0.5],
+                    [0.2, 0.9, 0.2, 0.5],
+                    [0.2, 0.4, 0.9, 0.5],
+                    [0.7, 0.2, 0.9, 0.5],
+                ];
+                for (hitbox_index, hitbox) in fighter.active_hitboxes().iter().enumerate() {
+                    let color = hitbox_colors[hitbox_index % hitbox_colors.len()];
+                    let center = fighter.hitbox_position(hitbox);
+                    self.draw_game_circle(canvas, color, &center, hitbox.radius, true);
+                }
+            }
+
+            // The previous and current ECB, and the paths their points sweep along when checking for collisions.
+            if self.debug_layers.ecb {
+                let previous_points = Self::ecb_points(&fighter.previous_position, &fighter.previous_ecb);
+                let points = Self::ecb_points(&fighter.position, &fighter.ecb);
+                for point_index in 0..4 {
+                    let next_index = (point_index + 1) % 4;
+                    self.draw_game_line(canvas, [0.5, 0.3, 0.1, 1.0], &previous_points[point_index], &previous_points[next_index]);
+                    self.draw_game_line(canvas, [1.0, 0.5, 0.1, 1.0], &points[point_index], &points[next_index]);
+                    self.draw_game_line(canvas, [0.9, 0.9, 0.9, 1.0], &previous_points[point_index], &points[point_index]);
+                }
+            }
+
+            // Velocity is drawn a few frames long so it's easier to see, and each active hitbox
+            // shows the angle it launches at.
+            if self.debug_layers.vectors {
+                let velocity_scale = 5.0;
+                let middle = Point {
+                    x: fighter.position.x,
+                    y: fighter.position.y + 0.5 * (fighter.ecb.bottom.y + fighter.ecb.top.y),
+                };
+                let velocity_end = Point {
+                    x: middle.x + velocity_scale * fighter.velocity.x,
+                    y: middle.y + velocity_scale * fighter.velocity.y,
+                };
+                self.draw_game_line(canvas, [0.2, 0.9, 0.9, 1.0], &middle, &velocity_end);
+
+                for hitbox in fighter.active_hitboxes() {
+                    let center = fighter.hitbox_position(hitbox);
+                    let angle = hitbox.angle.to_radians();
+                    let launch_end = Point {
+                        x: center.x + fighter.facing_direction() * hitbox.radius * angle.cos(),
+                        y: center.y + hitbox.radius * angle.sin(),
+                    };
+                    self.draw_game_line(canvas, [0.9, 0.9, 0.9, 1.0], &center, &launch_end);
+                }
+            }
+        }
+    }
+
+    // White for no DI, green for DI in and red for DI out, with a circle where each one
+    // would go past the blast zone.
+    fn draw_knockback_previews(
+        &self,
+        canvas: &mut impl Canvas,
+        game: &FightingGame,
+        training_mode: &TrainingMode,
+    ) {
+        for preview in training_mode.knockback_previews(game) {
+            let color = match preview.di_behavior {
+                DIBehavior::In => [0.2, 0.9, 0.2, 0.8],
+                DIBehavior::Out => [0.9, 0.2, 0.2, 0.8],
+                _ => [0.9, 0.9, 0.9, 0.8],
+            };
+            for points in preview.trajectory.points.windows(2) {
+                self.draw_game_line(canvas, color, &points[0], &points[1]);
+            }
+            if let Some(crossing) = &preview.trajectory.blast_zone_crossing {
+                self.draw_game_circle(canvas, color, crossing, 3.0, false);
+            }
+        }
+    }
+
+    // In drawing order around the diamond.
+    fn ecb_points(position: &Point, ecb: &ECB) -> [Point; 4] {
+        let offset = |point: &Point| Point { x: position.x + point.x, y: position.y + point.y };
+        [offset(&ecb.bottom), offset(&ecb.left), offset(&ecb.top), offset(&ecb.right)]
+    }
+
     fn draw_debug_text(
-        &mut self,
-        context: Context,
-        graphics: &mut G2d,
-        device: &mut gfx_device_gl::Device,
+        &self,
+        canvas: &mut impl Canvas,
+        game: &FightingGame,
+        possible_reload_error: &Option<String>,
+        possible_training_mode: Option<&TrainingMode>,
+        rewind: &Rewind,
+    ) {
+        let color = [0.2, 0.9, 0.2, 1.0];
+
+        let [x, y] = Anchor::TopLeft.position(10.0, 20.0);
+        self.draw_frame_data_overlay(canvas, game, x, y);
+        let [x, y] = Anchor::BottomRight.position(-230.0, -110.0);
+        self.draw_input_display(canvas, &game.players[0].input, x, y);
+
+        let stocks_text = game.players.iter().enumerate()
+            .map(|(player_index, player)| format!("P{}: {} {:.0}%", player_index + 1, player.stocks, player.fighter.percent))
+            .collect::<Vec<String>>()
+            .join("  ");
+        let [x, y] = Anchor::Bottom.position(-100.0, -70.0);
+        canvas.draw_text(&stocks_text, 20, color, x, y);
+
+        if let Some(frames_left) = game.frames_left() {
+            let seconds_left = frames_left.div_ceil(60);
+            let timer_text = format!("{}:{:02}", seconds_left / 60, seconds_left % 60);
+            let [x, y] = Anchor::Top.position(-25.0, 30.0);
+            canvas.draw_text(&timer_text, 20, color, x, y);
+        }
+
+        let possible_banner_text = match game.match_state {
+            MatchState::Countdown => Some(format!("{}", 3 - game.match_state_frame / 60)),
+            MatchState::Playing if game.match_frame < 60 => Some(String::from("GO!")),
+            MatchState::Playing => None,
+            MatchState::Paused if rewind.frames_back() > 0 => Some(format!("P{} paused, {} frames back", game.paused_by + 1, rewind.frames_back())),
+            MatchState::Paused => Some(format!("P{} paused", game.paused_by + 1)),
+            MatchState::GameOver => match &game.results {
+                Some(results) if results.was_quit => Some(String::from("No contest")),
+                Some(MatchResults { winner: Some(player_index), .. }) => Some(format!("GAME! P{} wins", player_index + 1)),
+                _ => Some(String::from("GAME!")),
+            },
+        };
+        if let Some(banner_text) = possible_banner_text {
+            let [x, y] = Anchor::Center.position(-100.0, 0.0);
+            canvas.draw_text(&banner_text, 40, color, x, y);
+        }
+
+        if game.match_state == MatchState::Countdown {
+            let rules = &game.rules;
+            let time_text = rules.time_limit_seconds
+                .map(|seconds| format!("{}:{:02}", seconds / 60, seconds % 60))
+                .unwrap_or_else(|| String::from("no time limit"));
+            let rules_text = format!(
+                "{} stocks, {}, items {}",
+                rules.stock_count,
+                time_text,
+                if rules.items { "on" } else { "off" },
+            );
+            let [x, y] = Anchor::Center.position(-100.0, 30.0);
+            canvas.draw_text(&rules_text, 16, color, x, y);
+        }
+
+        if let Some(results) = &game.results {
+            let seconds_played = results.frames_played / 60;
+            let [x, mut y] = Anchor::Center.position(-200.0, 40.0);
+            canvas.draw_text(&format!("Time {}:{:02}", seconds_played / 60, seconds_played % 60), 16, color, x, y);
+            for (player_index, player_results) in results.players.iter().enumerate() {
+                y += 22.0;
+                let l_cancel_text = player_results.l_cancel_rate
+                    .map(|rate| format!("{:.0}%", rate * 100.0))
+                    .unwrap_or_else(|| String::from("-"));
+                let results_text = format!(
+                    "P{}  Stocks: {}  KOs: {}  Falls: {}  Damage: {:.0}%  L-cancels: {}",
+                    player_index + 1,
+                    player_results.stocks_remaining,
+                    player_results.kos,
+                    player_results.falls,
+                    player_results.damage_dealt,
+                    l_cancel_text,
+                );
+                canvas.draw_text(&results_text, 16, color, x, y);
+            }
+        }
+
+        let layers = [
+            ("F1 Hurtboxes", self.debug_layers.hurtboxes),
+            ("F2 Hitboxes", self.debug_layers.hitboxes),
+            ("F3 ECB", self.debug_layers.ecb),
+            ("F4 Vectors", self.debug_layers.vectors),
+            ("F5 Normals", self.debug_layers.normals),
+        ];
+        let [mut layer_x, layer_y] = Anchor::TopLeft.position(10.0, 160.0);
+        for (name, is_on) in layers.iter() {
+            let layer_color = if *is_on { color } else { [0.3, 0.3, 0.3, 1.0] };
+            canvas.draw_text(name, 14, layer_color, layer_x, layer_y);
+            layer_x += 9.0 * name.len() as f64 + 15.0;
+        }
+        canvas.draw_text(&format!("C Camera: {:?}", self.camera.mode), 14, color, layer_x, layer_y);
+
+        if self.show_input_history {
+            let [x, y] = Anchor::TopRight.position(-330.0, 20.0);
+            self.draw_input_history(canvas, x, y);
+        }
+
+        if let Some(training_mode) = possible_training_mode {
+            let training_text = format!(
+                "Dummy: {:?}  DI: {:?}  Tech: {:?}  Savestate: {}",
+                training_mode.dummy_behavior,
+                training_mode.di_behavior,
+                training_mode.tech_behavior,
+                if training_mode.savestate.is_some() { "saved" } else { "none" },
+            );
+            let [x, y] = Anchor::BottomLeft.position(10.0, -10.0);
+            canvas.draw_text(&training_text, 14, color, x, y);
+
+            // DI only changes the launch angle, so every preview has the same hitstun.
+            let possible_hitstun_frames = training_mode.knockback_previews(game).first()
+                .map(|preview| preview.trajectory.hitstun_frames);
+            let preview_text = match (training_mode.preview_name(game), possible_hitstun_frames) {
+                (Some(name), Some(hitstun_frames)) => format!("Preview: {}, hitstun {}", name, hitstun_frames),
+                (Some(name), None) => format!("Preview: {}", name),
+                (None, _) => String::from("Preview: none"),
+            };
+            canvas.draw_text(&preview_text, 14, color, x, y - 18.0);
+            if let Some(timing_text) = training_mode.preview_timing(game) {
+                canvas.draw_text(&timing_text, 14, color, x, y - 36.0);
+            }
+        }
+
+        // Show why a data file failed to reload until it is fixed.
+        if let Some(reload_error) = possible_reload_error {
+            let [x, y] = Anchor::TopLeft.position(10.0, 180.0);
+            for (line_index, error_line) in reload_error.lines().enumerate() {
+                canvas.draw_text(error_line, 14, [0.9, 0.3, 0.3, 1.0], x, y + 18.0 * line_index as f64);
+            }
+        }
+    }
+
+    // Frame data for player 1, one line each.
+    fn draw_frame_data_overlay(
+        &self,
+        canvas: &mut impl Canvas,
         game: &FightingGame,
-        window_width: f64,
-        window_height: f64,
+        x: f64,
+        y: f64,
+    ) {
+        let color = [0.2, 0.9, 0.2, 1.0];
+        let line_spacing = 20.0;
+        let player = &game.players[0];
+        let fighter = &player.fighter;
+
+        let state_length_text = match fighter.state_total_frames() {
+            Some(total_frames) => format!("{} / {}", fighter.state_frame, total_frames),
+            None => format!("{}", fighter.state_frame),
+        };
+        let actionable_text = match (fighter.first_actionable_frame(), fighter.frames_until_actionable()) {
+            (Some(_), Some(0)) => String::from("now"),
+            (Some(frame), Some(frames_left)) => format!("frame {} ({} left)", frame, frames_left),
+            _ => String::from("not in this state"),
+        };
+        let advantage_text = match &game.frame_advantage {
+            Some(advantage) => format!(
+                "P{} {:+} on {} (P{})",
+                advantage.attacker + 1,
+                advantage.frames,
+                if advantage.on_shield { "shield" } else { "hit" },
+                advantage.defender + 1,
+            ),
+            None => String::from("--"),
+        };
+        let l_cancel_text = if player.l_cancel_landings > 0 {
+            format!(
+                "{}, {} / {} ({:.0}%)",
+                if fighter.last_landing_was_l_cancelled == Some(true) { "yes" } else { "no" },
+                player.l_cancels,
+                player.l_cancel_landings,
+                100.0 * player.l_cancels as f64 / player.l_cancel_landings as f64,
+            )
+        } else {
+            String::from("--")
+        };
+
+        let lines = [
+            format!("{}  {}", fighter.name, fighter.state_as_string()),
+            format!("State frame: {}", state_length_text),
+            format!("Actionable: {}", actionable_text),
+            format!("Advantage: {}", advantage_text),
+            format!("L-cancel: {}", l_cancel_text),
+            format!("Velocity: {:.5}, {:.5}", fighter.velocity.x, fighter.velocity.y),
+            format!("Stick: {:.4}, {:.4}", player.input.x_axis.value, player.input.y_axis.value),
+        ];
+        for (line_index, line) in lines.iter().enumerate() {
+            canvas.draw_text(line, 16, color, x, y + line_spacing * line_index as f64);
+        }
+    }
+
+    // Newest inputs at the top.
+    fn draw_input_history(
+        &self,
+        canvas: &mut impl Canvas,
+        x: f64,
+        y: f64,
     ) {
-        let debug_text_pixel_x = 0.5 * window_width;
-        let debug_text_pixel_y = 0.5 * window_height + 250.0;
         let color = [0.2, 0.9, 0.2, 1.0];
-        let x_spacing = 150.0;
-        let y_spacing = 25.0;
-        let offset = 50.0;
-
-        Text::new_color(color, 20).draw(
-            &game.player.state_as_string()[..],
-            &mut self.glyphs,
-            &context.draw_state,
-            context.transform.trans(offset + debug_text_pixel_x, debug_text_pixel_y),
-            graphics,
-        ).unwrap();
-
-        Text::new_color(color, 20).draw(
-            &format!("{}", game.player.state_frame)[..],
-            &mut self.glyphs,
-            &context.draw_state,
-            context.transform.trans(offset + debug_text_pixel_x, debug_text_pixel_y + y_spacing),
-            graphics,
-        ).unwrap();
-
-        Text::new_color(color, 20).draw(
-            &format!("{:.5}", game.player.velocity.x)[..],
-            &mut self.glyphs,
-            &context.draw_state,
-            context.transform.trans(offset + debug_text_pixel_x - x_spacing, debug_text_pixel_y + y_spacing),
-            graphics,
-        ).unwrap();
-
-        Text::new_color(color, 20).draw(
-            &format!("{:.5}", game.player.velocity.y)[..],
-            &mut self.glyphs,
-            &context.draw_state,
-            context.transform.trans(offset + debug_text_pixel_x - x_spacing, debug_text_pixel_y),
-            graphics,
-        ).unwrap();
-
-        Text::new_color(color, 20).draw(
-            &format!("{:.4}", game.input.x_axis.value)[..],
-            &mut self.glyphs,
-            &context.draw_state,
-            context.transform.trans(offset + debug_text_pixel_x - 2.0 * x_spacing, debug_text_pixel_y + y_spacing),
-            graphics,
-        ).unwrap();
-
-        Text::new_color(color, 20).draw(
-            &format!("{:.4}", game.input.y_axis.value)[..],
-            &mut self.glyphs,
-            &context.draw_state,
-            context.transform.trans(offset + debug_text_pixel_x - 2.0 * x_spacing, debug_text_pixel_y),
-            graphics,
-        ).unwrap();
-
-        self.glyphs.factory.encoder.flush(device);
+        let line_spacing = 16.0;
+        let lines: Vec<String> = self.input_history.entries.iter().rev()
+            .map(|entry| entry.to_line())
+            .collect();
+
+        canvas.draw_text("Frames  Stick  Axes (active)  Buttons", 14, color, x, y);
+        for (line_index, line) in lines.iter().enumerate() {
+            canvas.draw_text(line, 14, color, x, y + line_spacing * (line_index + 1) as f64);
+        }
+    }
+
+    // The control stick and C-stick inside their gates, then the buttons, which light up while held.
+    fn draw_input_display(
+        &self,
+        canvas: &mut impl Canvas,
+        input: &ControllerState,
+        x: f64,
+        y: f64,
+    ) {
+        let gate_radius = 40.0;
+        let c_gate_radius = 25.0;
+        let stick_center = [x + gate_radius, y + gate_radius];
+        let c_stick_center = [x + 2.0 * gate_radius + 20.0 + c_gate_radius, y + gate_radius];
+        self.draw_stick(canvas, stick_center, gate_radius, input.x_axis.value, input.y_axis.value, [0.9, 0.9, 0.9, 1.0]);
+        self.draw_stick(canvas, c_stick_center, c_gate_radius, input.c_x_axis.value, input.c_y_axis.value, [0.9, 0.8, 0.2, 1.0]);
+
+        let buttons = [
+            ("A", input.a_button.is_pressed, [0.2, 0.8, 0.6, 1.0]),
+            ("B", input.b_button.is_pressed, [0.9, 0.3, 0.3, 1.0]),
+            ("X", input.x_button.is_pressed, [0.8, 0.8, 0.8, 1.0]),
+            ("Y", input.y_button.is_pressed, [0.8, 0.8, 0.8, 1.0]),
+            ("Z", input.z_button.is_pressed, [0.5, 0.3, 0.9, 1.0]),
+            ("L", input.l_button.is_pressed, [0.8, 0.8, 0.8, 1.0]),
+            ("R", input.r_button.is_pressed, [0.8, 0.8, 0.8, 1.0]),
+            ("S", input.start_button.is_pressed, [0.8, 0.8, 0.8, 1.0]),
+        ];
+        let button_spacing = 22.0;
+        let buttons_y = y + 2.0 * gate_radius + 22.0;
+        for (button_index, (name, is_pressed, lit_color)) in buttons.iter().enumerate() {
+            let color = if *is_pressed { *lit_color } else { [0.3, 0.3, 0.3, 1.0] };
+            canvas.draw_text(name, 18, color, x + button_spacing * button_index as f64, buttons_y);
+        }
+    }
+
+    fn draw_stick(
+        &self,
+        canvas: &mut impl Canvas,
+        center: [f64; 2],
+        radius: f64,
+        x_value: f64,
+        y_value: f64,
+        color: [f32; 4],
+    ) {
+        // Octagonal gate like on a GameCube controller.
+        let gate_color = [0.4, 0.4, 0.4, 1.0];
+        for corner_index in 0..8 {
+            let angle = corner_index as f64 * std::f64::consts::PI / 4.0;
+            let next_angle = (corner_index + 1) as f64 * std::f64::consts::PI / 4.0;
+            canvas.draw_line(
+                gate_color,
+                0.5,
+                [
+                    center[0] + radius * angle.cos(),
+                    center[1] - radius * angle.sin(),
+                    center[0] + radius * next_angle.cos(),
+                    center[1] - radius * next_angle.sin(),
+                ],
+            );
+        }
+
+        let stick_x = center[0] + radius * x_value;
+        let stick_y = center[1] - radius * y_value;
+        canvas.draw_line(gate_color, 0.5, [center[0], center[1], stick_x, stick_y]);
+        let dot_radius = 4.0;
+        canvas.draw_circle(color, [stick_x, stick_y], dot_radius, true);
     }
 
     fn draw_poly_line(
         &self,
-        context: Context,
-        graphics: &mut G2d,
-        window_width: f64,
-        window_height: f64,
-        poly_line: &PolyLine,
+        canvas: &mut impl Canvas,
+        poly_line: &CollisionPolyLine,
     ) {
-        for line_segment in &poly_line.segments {
+        for collision_line in &poly_line.lines {
+            let line_segment = &collision_line.segment;
             let color = {
-                //if collision_line.is_ground() { [0.3, 0.3, 0.3, 1.0] }
-                //else { [0.9, 0.3, 0.3, 1.0] }
-                [0.3, 0.3, 0.3, 1.0]
+                if collision_line.is_pass_through { [0.3, 0.3, 0.6, 1.0] }
+                else if collision_line.is_ground() { [0.3, 0.3, 0.3, 1.0] }
+                else { [0.9, 0.3, 0.3, 1.0] }
             };
 
             let left_point = line_segment.left_point();
             let right_point = line_segment.right_point();
 
-            line(
+            canvas.draw_line(
                 color,
                 1.0,
                 [
-                    self.game_x_to_screen_x(left_point.x, window_width),
-                    self.game_y_to_screen_y(left_point.y, window_height),
-                    self.game_x_to_screen_x(right_point.x, window_width),
-                    self.game_y_to_screen_y(right_point.y, window_height),
+                    self.game_x_to_screen_x(left_point.x),
+                    self.game_y_to_screen_y(left_point.y),
+                    self.game_x_to_screen_x(right_point.x),
+                    self.game_y_to_screen_y(right_point.y),
                 ],
-                context.transform.trans(0.0, 0.0),
-                graphics,
             );
         }
     }
 
     fn draw_collision_lines(
         &self,
-        context: Context,
-        graphics: &mut G2d,
-        collision_lines: &Vec<PolyLine>,
-        window_width: f64,
-        window_height: f64,
+        canvas: &mut impl Canvas,
+        collision_lines: &[CollisionPolyLine],
     ) {
         for poly_line in collision_lines {
-            self.draw_poly_line(context, graphics, window_width, window_height, &poly_line);
+            self.draw_poly_line(canvas, poly_line);
         }
     }
+
+    fn draw_blast_zone(
+        &self,
+        canvas: &mut impl Canvas,
+        blast_zone: &Bounds,
+    ) {
+        let left = self.game_x_to_screen_x(blast_zone.left);
+        let right = self.game_x_to_screen_x(blast_zone.right);
+        let bottom = self.game_y_to_screen_y(blast_zone.bottom);
+        let top = self.game_y_to_screen_y(blast_zone.top);
+        let color = [0.6, 0.2, 0.2, 1.0];
+        let radius = 0.5;
+        canvas.draw_line(color, radius, [left, top, right, top]);
+        canvas.draw_line(color, radius, [right, top, right, bottom]);
+        canvas.draw_line(color, radius, [right, bottom, left, bottom]);
+        canvas.draw_line(color, radius, [left, bottom, left, top]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::path::Path;
+
+    use super::*;
+    use crate::match_rules::MatchRules;
+    use crate::software_canvas::SoftwareCanvas;
+    use crate::stage::Stage;
+
+    // The first frame of training mode as "--training" starts it, drawn with the software
+    // canvas and compared to a checked in image. After an intended change to how things are
+    // drawn, record it again with
+    // "cargo run -- --training --render-png=assets/golden_images/training_v_stage_fox.png".
+    #[test]
+    fn training_frame_matches_the_golden_image() {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let stage = Stage::from_file(&assets.join("stages").join("v_stage.json")).unwrap();
+        let mut rules = MatchRules::default();
+        rules.stock_count = 99;
+        rules.time_limit_seconds = None;
+        let mut game = FightingGame::new(rules, stage, vec![Fighter::fox(), Fighter::fox()]);
+        game.change_match_state(MatchState::Playing);
+        let training_mode = TrainingMode::default();
+
+        let mut renderer = FightingGameRenderer::default();
+        let mut canvas = SoftwareCanvas::new(VIRTUAL_WIDTH as u32, VIRTUAL_HEIGHT as u32);
+        renderer.update_camera(&game);
+        renderer.render(&mut canvas, &RenderContext {
+            game: &game,
+            possible_reload_error: &None,
+            possible_training_mode: Some(&training_mode),
+            rewind: &Rewind::default(),
+            window_width: VIRTUAL_WIDTH,
+            window_height: VIRTUAL_HEIGHT,
+            interpolation: 1.0,
+        });
+
+        let decoder = png::Decoder::new(File::open(assets.join("golden_images").join("training_v_stage_fox.png")).unwrap());
+        let (info, mut reader) = decoder.read_info().unwrap();
+        let mut golden_pixels = vec![0; info.buffer_size()];
+        reader.next_frame(&mut golden_pixels).unwrap();
+        assert_eq!((info.width, info.height), (canvas.width, canvas.height));
+        let differing_pixels = canvas.pixels.chunks(4).zip(golden_pixels.chunks(4)).filter(|(pixel, golden_pixel)| pixel != golden_pixel).count();
+        assert_eq!(differing_pixels, 0, "the frame doesn't match the golden image");
+    }
+
+    fn window_position_of(renderer: &FightingGameRenderer, viewport: &Viewport, point: &Point) -> [f64; 2] {
+        viewport.window_position([renderer.game_x_to_screen_x(point.x), renderer.game_y_to_screen_y(point.y)])
+    }
+
+    fn mouse_event(input: Input) -> Event {
+        Event::Input(input, None)
+    }
+
+    fn middle_mouse(state: ButtonState) -> Event {
+        mouse_event(Input::Button(ButtonArgs { state, button: Button::Mouse(MouseButton::Middle), scancode: None }))
+    }
+
+    // In a window wider than the virtual screen, zoomed in and panned away from the start,
+    // the middle of the camera is still the middle of the window, and dragging with the
+    // middle mouse button moves whatever is under the cursor exactly as far as the mouse.
+    #[test]
+    fn panning_keeps_the_stage_under_the_mouse_at_any_zoom() {
+        let (window_width, window_height) = (1000.0, 600.0);
+        let viewport = Viewport::fit(VIRTUAL_WIDTH, VIRTUAL_HEIGHT, window_width, window_height);
+        let mut renderer = FightingGameRenderer::default();
+        renderer.handle_mouse_pan_and_zoom(&mouse_event(Input::Move(Motion::MouseScroll([0.0, 3.0]))), window_width, window_height);
+        renderer.handle_mouse_pan_and_zoom(&middle_mouse(ButtonState::Press), window_width, window_height);
+        renderer.handle_mouse_pan_and_zoom(&mouse_event(Input::Move(Motion::MouseRelative([37.0, -12.0]))), window_width, window_height);
+        assert_eq!(renderer.camera.mode, CameraMode::Free);
+        assert!((renderer.camera.view_height - 150.0 * 0.9_f64.powi(3)).abs() < 1e-9);
+
+        let center = Point { x: renderer.camera.x, y: renderer.camera.y };
+        let window_center = window_position_of(&renderer, &viewport, &center);
+        assert!((window_center[0] - 0.5 * window_width).abs() < 1e-9 && (window_center[1] - 0.5 * window_height).abs() < 1e-9);
+
+        let point = Point { x: 12.5, y: -7.25 };
+        let before = window_position_of(&renderer, &viewport, &point);
+        renderer.handle_mouse_pan_and_zoom(&mouse_event(Input::Move(Motion::MouseRelative([40.0, 25.0]))), window_width, window_height);
+        let after = window_position_of(&renderer, &viewport, &point);
+        assert!((after[0] - before[0] - 40.0).abs() < 1e-9 && (after[1] - before[1] - 25.0).abs() < 1e-9, "moved from {:?} to {:?}", before, after);
+
+        renderer.handle_mouse_pan_and_zoom(&middle_mouse(ButtonState::Release), window_width, window_height);
+        renderer.handle_mouse_pan_and_zoom(&mouse_event(Input::Move(Motion::MouseRelative([40.0, 25.0]))), window_width, window_height);
+        assert_eq!(window_position_of(&renderer, &viewport, &point), after);
+    }
 }
@@ -0,0 +1,475 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::controller_state::ControllerState;
+use crate::fighter::{Fighter, FighterState};
+use crate::fighting_game::{FightingGame, MatchState};
+use crate::match_rules::MatchRules;
+use crate::stage::Stage;
+
+// Headless frame data checks. Each check plays a scripted sequence of inputs and compares
+// the fighter on every frame against what was recorded, so any change to movement shows
+// up as a per frame diff. The recorded frames are golden snapshots of this game and say
+// nothing about Melee, and when movement is changed on purpose they can be recorded again.
+// Checks can also list reference numbers from Melee, which are entered by hand with their
+// source and never recorded over, so recording can't make the game drift away from Melee.
+
+pub enum FrameDataError {
+    Io(String, std::io::Error),
+    Parse(String, String, serde_json::Error),
+    Load(String, String),
+}
+
+impl fmt::Display for FrameDataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameDataError::Io(source, error) => write!(f, "Could not read frame data {}: {}", source, error),
+            FrameDataError::Parse(source, field, error) => write!(f, "Could not parse frame data {} at {}: {}", source, field, error),
+            FrameDataError::Load(source, error) => write!(f, "Could not set up frame data {}: {}", source, error),
+        }
+    }
+}
+
+impl fmt::Debug for FrameDataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// Values closer than this count as the same. The simulation is deterministic so this
+// only has to cover the references being written out and read back in.
+const TOLERANCE: f64 = 1e-9;
+
+// Reference numbers are written by hand with a few decimal places.
+const REFERENCE_TOLERANCE: f64 = 1e-4;
+
+// How many differing frames are printed per check before the rest are only counted.
+const MAX_PRINTED_DIFFERENCES: usize = 10;
+
+// Runs every frame data file in the folder and prints a report. Returns whether every
+// check matched. Recording overwrites the recorded frames with what happens now.
+pub fn run_folder(assets: &Path, should_record: bool) -> Result<bool, FrameDataError> {
+    let folder = assets.join("frame_data");
+    let source = folder.display().to_string();
+    let mut paths = fs::read_dir(&folder)
+        .map_err(|error| FrameDataError::Io(source.clone(), error))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let mut all_passed = true;
+    for path in &paths {
+        all_passed &= run_file(assets, path, should_record)?;
+    }
+    Ok(all_passed)
+}
+
+pub fn run_file(assets: &Path, path: &Path, should_record: bool) -> Result<bool, FrameDataError> {
+    let source = path.display().to_string();
+    let text = fs::read_to_string(path).map_err(|error| FrameDataError::Io(source.clone(), error))?;
+    let deserializer = &mut serde_json::Deserializer::from_str(&text);
+    let mut frame_data_file: FrameDataFile = serde_path_to_error::deserialize(deserializer)
+        .map_err(|error| FrameDataError::Parse(
+            source.clone(),
+            error.path().to_string(),
+            error.into_inner(),
+        ))?;
+
+    let character_path = assets.join("characters").join(format!("{}.json", frame_data_file.character));
+    let stage_path = assets.join("stages").join(format!("{}.json", frame_data_file.stage));
+    let character_source = character_path.display().to_string();
+    let character_text = fs::read_to_string(&character_path).map_err(|error| FrameDataError::Io(character_source.clone(), error))?;
+    // Each check gets a fresh fighter, but a bad character file should be reported up front.
+    Fighter::from_json(&character_text, &character_source).map_err(|error| FrameDataError::Load(source.clone(), error.to_string()))?;
+    let stage = Stage::from_file(&stage_path).map_err(|error| FrameDataError::Load(source.clone(), error.to_string()))?;
+    for (check_index, check) in frame_data_file.checks.iter().enumerate() {
+        if check.reference.as_ref().is_some_and(|reference| reference.source.trim().is_empty()) {
+            return Err(FrameDataError::Load(source.clone(), format!("checks[{}].reference.source has to say where the numbers came from.", check_index)));
+        }
+    }
+
+    println!("{}", source);
+    let mut all_passed = true;
+    for check in &mut frame_data_file.checks {
+        let fighter = Fighter::from_json(&character_text, &character_source).unwrap();
+        let actual = play_check(fighter, &stage, check);
+        if should_record {
+            println!("    {}: recorded {} frames, {}", check.name, actual.len(), Summary::from_frames(&actual));
+            check.recorded = actual;
+        }
+        else {
+            all_passed &= report_check(check, &actual);
+        }
+    }
+
+    if should_record {
+        fs::write(path, frame_data_file.to_json()).map_err(|error| FrameDataError::Io(source.clone(), error))?;
+    }
+
+    Ok(all_passed)
+}
+
+// Each check starts from a fresh match that skips the countdown.
+fn play_check(fighter: Fighter, stage: &Stage, check: &CheckFile) -> Vec<FrameFile> {
+    let mut game = FightingGame::new(MatchRules::default(), stage.clone(), vec![fighter]);
+    game.change_match_state(MatchState::Playing);
+
+    let mut frames = Vec::new();
+    let mut input = ControllerState::default();
+    for input_file in &check.inputs {
+        input_file.apply_to(&mut input);
+        for _ in 0..input_file.frames {
+            game.update(&[&input]);
+            frames.push(FrameFile::from_fighter(&game.players[0].fighter));
+        }
+    }
+    frames
+}
+
+fn report_check(check: &CheckFile, actual: &[FrameFile]) -> bool {
+    let mut differences = Vec::new();
+    for frame in 0..check.recorded.len().max(actual.len()) {
+        let recorded_frame = check.recorded.get(frame);
+        let actual_frame = actual.get(frame);
+        let is_same = match (recorded_frame, actual_frame) {
+            (Some(recorded_frame), Some(actual_frame)) => recorded_frame.matches(actual_frame),
+            _ => false,
+        };
+        if !is_same {
+            differences.push((frame, recorded_frame, actual_frame));
+        }
+    }
+
+    let summary = Summary::from_frames(actual);
+    let reference_differences = check.reference.as_ref()
+        .map_or_else(Vec::new, |reference| reference.differences(&summary));
+
+    if differences.is_empty() && reference_differences.is_empty() {
+        println!("    {}: ok, {}", check.name, summary);
+        return true;
+    }
+
+    if differences.is_empty() {
+        println!("    {}: FAILED against the Melee reference", check.name);
+        println!("        actual   {}", summary);
+    }
+    else {
+        println!("    {}: FAILED on {} of {} recorded frames", check.name, differences.len(), check.recorded.len().max(actual.len()));
+        println!("        recorded {}", Summary::from_frames(&check.recorded));
+        println!("        actual   {}", summary);
+    }
+    for reference_difference in &reference_differences {
+        println!("        {}", reference_difference);
+    }
+    for (frame, recorded_frame, actual_frame) in differences.iter().take(MAX_PRINTED_DIFFERENCES) {
+        println!("        frame {}", frame);
+        println!("            - {}", recorded_frame.map_or(String::from("nothing"), |frame| frame.to_string()));
+        println!("            + {}", actual_frame.map_or(String::from("nothing"), |frame| frame.to_string()));
+    }
+    if differences.len() > MAX_PRINTED_DIFFERENCES {
+        println!("        ...and {} more frames", differences.len() - MAX_PRINTED_DIFFERENCES);
+    }
+    false
+}
+
+// The numbers people usually compare against Melee, worked out from a run of frames.
+struct Summary {
+    distance: f64,
+    apex_height: f64,
+    airtime: u32,
+    state_frames: Vec<(FighterState, u32)>,
+}
+
+impl Summary {
+    fn from_frames(frames: &[FrameFile]) -> Self {
+        let mut summary = Self {
+            distance: 0.0,
+            apex_height: 0.0,
+            airtime: 0,
+            state_frames: Vec::new(),
+        };
+        if let (Some(first), Some(last)) = (frames.first(), frames.last()) {
+            summary.distance = last.x - first.x;
+        }
+        let start_height = frames.first().map_or(0.0, |frame| frame.y);
+        for frame in frames {
+            summary.apex_height = summary.apex_height.max(frame.y - start_height);
+            if is_airborne_state(frame.state) {
+                summary.airtime += 1;
+            }
+            match summary.state_frames.last_mut() {
+                Some((state, count)) if *state == frame.state => *count += 1,
+                _ => summary.state_frames.push((frame.state, 1)),
+            }
+        }
+        summary
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "distance {:.4}, apex {:.4}, airtime {}, states", self.distance, self.apex_height, self.airtime)?;
+        for (state, count) in &self.state_frames {
+            write!(f, " {:?}({})", state, count)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_airborne_state(state: FighterState) -> bool {
+    state == FighterState::Airborne
+    || state == FighterState::AirDodge
+    || state == FighterState::FallThrough
+    || state == FighterState::Float
+    || state == FighterState::AerialAttack
+    || state == FighterState::Hitstun
+}
+
+// Everything below mirrors the layout of the frame data files.
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FrameDataFile {
+    character: String,
+    stage: String,
+    checks: Vec<CheckFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CheckFile {
+    name: String,
+    inputs: Vec<InputFile>,
+    #[serde(default)]
+    reference: Option<ReferenceFile>,
+    #[serde(default)]
+    recorded: Vec<FrameFile>,
+}
+
+// Numbers from Melee that the check has to hit. Anything left out isn't checked.
+// State frames are how long the first stretch of each listed state lasts. The source
+// has to say where the numbers came from, and how they were worked out if they aren't
+// taken straight from it.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReferenceFile {
+    source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    distance: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    apex_height: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    airtime: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    state_frames: Vec<StateFramesFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StateFramesFile {
+    state: FighterState,
+    frames: u32,
+}
+
+// Inputs are held for the given number of frames. Anything not listed is released.
+// Replays write their inputs the same way.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputFile {
+    pub frames: u32,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub x: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub y: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buttons: Vec<ButtonName>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+pub enum ButtonName {
+    A,
+    B,
+    X,
+    Y,
+    Z,
+    L,
+    R,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FrameFile {
+    state: FighterState,
+    x: f64,
+    y: f64,
+    vx: f64,
+    vy: f64,
+}
+
+fn is_zero(value: &f64) -> bool {
+    *value == 0.0
+}
+
+// Written by hand so every input and frame stays on its own line, which keeps the
+// files readable and makes re-recorded references diff nicely.
+impl FrameDataFile {
+    fn to_json(&self) -> String {
+        let mut json = String::from("{\n");
+        json += &format!("    \"character\": {},\n", serde_json::to_string(&self.character).unwrap());
+        json += &format!("    \"stage\": {},\n", serde_json::to_string(&self.stage).unwrap());
+        json += "    \"checks\": [\n";
+        for (check_index, check) in self.checks.iter().enumerate() {
+            json += "        {\n";
+            json += &format!("            \"name\": {},\n", serde_json::to_string(&check.name).unwrap());
+            json += "            \"inputs\": [\n";
+            json += &inline_json_lines(&check.inputs, 16);
+            json += "            ],\n";
+            if let Some(reference) = &check.reference {
+                json += &reference.to_json();
+            }
+            json += "            \"recorded\": [\n";
+            json += &inline_json_lines(&check.recorded, 16);
+            json += "            ]\n";
+            json += if check_index + 1 < self.checks.len() { "        },\n" } else { "        }\n" };
+        }
+        json += "    ]\n";
+        json += "}\n";
+        json
+    }
+}
+
+// Only meant for the small objects in these files, none of which have strings with
+// punctuation in them.
+fn inline_json_lines<T: Serialize>(values: &[T], indent: usize) -> String {
+    let mut lines = String::new();
+    for (value_index, value) in values.iter().enumerate() {
+        let compact = serde_json::to_string(value).unwrap();
+        let spaced = compact
+            .replace(":", ": ")
+            .replace(",", ", ")
+            .replace("{", "{ ")
+            .replace("}", " }");
+        let separator = if value_index + 1 < values.len() { "," } else { "" };
+        lines += &format!("{}{}{}\n", " ".repeat(indent), spaced, separator);
+    }
+    lines
+}
+
+impl InputFile {
+    pub fn apply_to(&self, input: &mut ControllerState) {
+        input.x_axis.value = self.x;
+        input.y_axis.value = self.y;
+        input.a_button.is_pressed = self.buttons.contains(&ButtonName::A);
+        input.b_button.is_pressed = self.buttons.contains(&ButtonName::B);
+        input.x_button.is_pressed = self.buttons.contains(&ButtonName::X);
+        input.y_button.is_pressed = self.buttons.contains(&ButtonName::Y);
+        input.z_button.is_pressed = self.buttons.contains(&ButtonName::Z);
+        input.l_button.is_pressed = self.buttons.contains(&ButtonName::L);
+        input.r_button.is_pressed = self.buttons.contains(&ButtonName::R);
+    }
+}
+
+impl FrameFile {
+    fn from_fighter(fighter: &Fighter) -> Self {
+        Self {
+            state: fighter.state,
+            x: fighter.position.x,
+            y: fighter.position.y,
+            vx: fighter.velocity.x,
+            vy: fighter.velocity.y,
+        }
+    }
+
+    fn matches(&self, other: &Self) -> bool {
+        self.state == other.state
+        && (self.x - other.x).abs() <= TOLERANCE
+        && (self.y - other.y).abs() <= TOLERANCE
+        && (self.vx - other.vx).abs() <= TOLERANCE
+        && (self.vy - other.vy).abs() <= TOLERANCE
+    }
+}
+
+impl fmt::Display for FrameFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} x={:.6} y={:.6} vx={:.6} vy={:.6}", self.state, self.x, self.y, self.vx, self.vy)
+    }
+}
+
+impl ReferenceFile {
+    fn to_json(&self) -> String {
+        let mut fields = vec![format!("\"source\": {}", serde_json::to_string(&self.source).unwrap())];
+        if let Some(distance) = self.distance {
+            fields.push(format!("\"distance\": {}", serde_json::to_string(&distance).unwrap()));
+        }
+        if let Some(apex_height) = self.apex_height {
+            fields.push(format!("\"apex_height\": {}", serde_json::to_string(&apex_height).unwrap()));
+        }
+        if let Some(airtime) = self.airtime {
+            fields.push(format!("\"airtime\": {}", airtime));
+        }
+        if !self.state_frames.is_empty() {
+            fields.push(format!(
+                "\"state_frames\": [\n{}                ]",
+                inline_json_lines(&self.state_frames, 20),
+            ));
+        }
+
+        let mut json = String::from("            \"reference\": {\n");
+        json += &fields.iter()
+            .map(|field| format!("                {}", field))
+            .collect::<Vec<_>>()
+            .join(",\n");
+        json += "\n            },\n";
+        json
+    }
+
+    // Describes every way the summary misses the reference.
+    fn differences(&self, summary: &Summary) -> Vec<String> {
+        let mut differences = Vec::new();
+        if let Some(distance) = self.distance {
+            if (summary.distance - distance).abs() > REFERENCE_TOLERANCE {
+                differences.push(format!("distance should be {:.4} but is {:.4}", distance, summary.distance));
+            }
+        }
+        if let Some(apex_height) = self.apex_height {
+            if (summary.apex_height - apex_height).abs() > REFERENCE_TOLERANCE {
+                differences.push(format!("apex should be {:.4} but is {:.4}", apex_height, summary.apex_height));
+            }
+        }
+        if let Some(airtime) = self.airtime {
+            if summary.airtime != airtime {
+                differences.push(format!("airtime should be {} but is {}", airtime, summary.airtime));
+            }
+        }
+        for state_frames in &self.state_frames {
+            let possible_frames = summary.state_frames.iter()
+                .find(|(state, _)| *state == state_frames.state)
+                .map(|(_, frames)| *frames);
+            match possible_frames {
+                Some(frames) if frames == state_frames.frames => (),
+                Some(frames) => differences.push(format!("{:?} should last {} frames but lasts {}", state_frames.state, state_frames.frames, frames)),
+                None => differences.push(format!("{:?} should last {} frames but never happens", state_frames.state, state_frames.frames)),
+            }
+        }
+        differences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    // Runs every file in assets/frame_data, so any change to movement or any miss against
+    // the Melee references fails the tests. The report is printed when it fails.
+    #[test]
+    fn frame_data_matches() {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        assert!(run_folder(&assets, false).unwrap(), "frame data changed, see the report above");
+    }
+}
@@ -0,0 +1,168 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::fighter::Fighter;
+use crate::fighting_game::FightingGame;
+use crate::stage::Stage;
+
+// Watches the stage and character files the game was started with and reloads
+// them when they change. Files are polled since there is no need to know the
+// instant a file changes, only by the next frame.
+pub struct HotReloader {
+    pub stage_file: WatchedFile,
+    pub character_files: Vec<WatchedFile>,
+    pub error: Option<String>,
+    pub poll_interval: Duration,
+    pub last_poll: Instant,
+}
+
+// Each file keeps its own error, so loading one file fine doesn't hide that another
+// one is still broken.
+pub struct WatchedFile {
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    pub error: Option<String>,
+}
+
+impl WatchedFile {
+    pub fn new(path: PathBuf) -> Self {
+        let modified = Self::read_modified(&path);
+        Self {
+            path,
+            modified,
+            error: None,
+        }
+    }
+
+    // Editors often write files in more than one step, so a file that can't be
+    // read right now is just checked again later.
+    pub fn has_changed(&mut self) -> bool {
+        let modified = Self::read_modified(&self.path);
+        if modified.is_some() && modified != self.modified {
+            self.modified = modified;
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    fn read_modified(path: &PathBuf) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+    }
+}
+
+impl HotReloader {
+    // The character files are in player order.
+    pub fn new(stage_path: PathBuf, character_paths: Vec<PathBuf>) -> Self {
+        Self {
+            stage_file: WatchedFile::new(stage_path),
+            character_files: character_paths.into_iter().map(WatchedFile::new).collect(),
+            error: None,
+            poll_interval: Duration::from_millis(250),
+            last_poll: Instant::now(),
+        }
+    }
+
+    // Should be called between frames. Anything that fails to load is left as it was
+    // and the error is kept to be shown until the file loads successfully.
+    pub fn update(&mut self, game: &mut FightingGame) {
+        if self.last_poll.elapsed() < self.poll_interval {
+            return;
+        }
+        self.last_poll = Instant::now();
+
+        if self.stage_file.has_changed() {
+            match Stage::from_file(&self.stage_file.path) {
+                Ok(stage) => {
+                    let frame = game.stage.frame;
+                    game.stage = stage;
+                    game.stage.set_frame(frame);
+                    self.stage_file.error = None;
+                },
+                Err(error) => self.stage_file.error = Some(error.to_string()),
+            }
+        }
+
+        for (player, character_file) in game.players.iter_mut().zip(&mut self.character_files) {
+            if character_file.has_changed() {
+                match Fighter::from_file(&character_file.path) {
+                    Ok(character) => {
+                        player.fighter.apply_character(&character);
+                        character_file.error = None;
+                    },
+                    Err(error) => character_file.error = Some(error.to_string()),
+                }
+            }
+        }
+
+        // Players can share a character file, which only needs to be shown once.
+        let mut errors: Vec<&str> = Vec::new();
+        for error in std::iter::once(&self.stage_file).chain(&self.character_files).filter_map(|file| file.error.as_deref()) {
+            if !errors.contains(&error) {
+                errors.push(error);
+            }
+        }
+        self.error = if errors.is_empty() { None } else { Some(errors.join("\n")) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::path::Path;
+
+    use super::*;
+    use crate::fighting_game::MatchState;
+    use crate::match_rules::MatchRules;
+
+    // Copies an asset into a folder of its own so the test can break it.
+    fn copy_asset(folder: &Path, kind: &str, name: &str) -> PathBuf {
+        let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join(kind).join(name);
+        let path = folder.join(name);
+        fs::copy(source, &path).unwrap();
+        path
+    }
+
+    // Writes the file and moves its modified time forward, so it counts as changed even
+    // when the file system only keeps whole seconds.
+    fn write_changed(path: &Path, text: &str, seconds_later: u64) {
+        fs::write(path, text).unwrap();
+        let modified = SystemTime::now() + Duration::from_secs(seconds_later);
+        File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn a_good_character_keeps_a_bad_stage_error() {
+        let folder = std::env::temp_dir().join(format!("hot_reload_test_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let stage_path = copy_asset(&folder, "stages", "final_destination.json");
+        let character_path = copy_asset(&folder, "characters", "fox.json");
+        let character_text = fs::read_to_string(&character_path).unwrap();
+
+        let stage = Stage::from_file(&stage_path).unwrap();
+        let fighter = Fighter::from_file(&character_path).unwrap();
+        let mut game = FightingGame::new(MatchRules::default(), stage, vec![fighter]);
+        game.change_match_state(MatchState::Playing);
+        let mut hot_reloader = HotReloader::new(stage_path.clone(), vec![character_path.clone()]);
+        hot_reloader.poll_interval = Duration::ZERO;
+
+        write_changed(&stage_path, "{", 10);
+        hot_reloader.update(&mut game);
+        let stage_error = hot_reloader.error.clone().expect("the bad stage should be reported");
+        assert!(stage_error.contains("final_destination.json"), "{}", stage_error);
+
+        write_changed(&character_path, &character_text, 20);
+        hot_reloader.update(&mut game);
+        assert_eq!(hot_reloader.error, Some(stage_error));
+        assert!(hot_reloader.character_files[0].error.is_none());
+
+        let stage_text = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/stages/final_destination.json")).unwrap();
+        write_changed(&stage_path, &stage_text, 30);
+        hot_reloader.update(&mut game);
+        assert_eq!(hot_reloader.error, None);
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::VecDeque;
+
+use crate::analog_axis::AnalogAxis;
+use crate::controller_state::ControllerState;
+
+// A log of what a controller held, one entry for each change to the stick zone or
+// buttons along with how many frames it was held for.
+pub struct InputHistory {
+    pub entries: VecDeque<InputHistoryEntry>,
+    pub max_entries: usize,
+}
+
+// Stick zones use numpad notation, so 5 is neutral, 6 is right, 8 is up and so on.
+// The axis values and frames active are from the first frame of the entry, which is
+// what decides whether a stick movement counted as a smash.
+pub struct InputHistoryEntry {
+    pub stick_zone: u8,
+    pub c_stick_zone: u8,
+    pub buttons: String,
+    pub x_value: f64,
+    pub y_value: f64,
+    pub x_frames_active: u32,
+    pub y_frames_active: u32,
+    pub frames: u32,
+}
+
+impl InputHistory {
+    pub fn default() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            max_entries: 24,
+        }
+    }
+
+    // Should be called once per game update with the input the game saw.
+    pub fn record(&mut self, input: &ControllerState) {
+        let stick_zone = find_stick_zone(&input.x_axis, &input.y_axis);
+        let c_stick_zone = find_stick_zone(&input.c_x_axis, &input.c_y_axis);
+        let buttons = find_held_buttons(input);
+
+        if let Some(entry) = self.entries.back_mut() {
+            if entry.stick_zone == stick_zone && entry.c_stick_zone == c_stick_zone && entry.buttons == buttons {
+                entry.frames += 1;
+                return;
+            }
+        }
+
+        self.entries.push_back(InputHistoryEntry {
+            stick_zone,
+            c_stick_zone,
+            buttons,
+            x_value: input.x_axis.value,
+            y_value: input.y_axis.value,
+            x_frames_active: input.x_axis.frames_active,
+            y_frames_active: input.y_axis.frames_active,
+            frames: 1,
+        });
+        if self.entries.len() > self.max_entries {
+            self.entries.pop_front();
+        }
+    }
+}
+
+impl InputHistoryEntry {
+    // Something like "  12  6  0.8000, 0.0000 (0, 0)  X R".
+    pub fn to_line(&self) -> String {
+        let c_stick_text = if self.c_stick_zone == 5 { String::new() } else { format!(" C{}", self.c_stick_zone) };
+        format!(
+            "{:>4}  {}{}  {:.4}, {:.4} ({}, {})  {}",
+            self.frames,
+            self.stick_zone,
+            c_stick_text,
+            self.x_value,
+            self.y_value,
+            self.x_frames_active,
+            self.y_frames_active,
+            self.buttons,
+        )
+    }
+}
+
+fn find_stick_zone(x_axis: &AnalogAxis, y_axis: &AnalogAxis) -> u8 {
+    let column = if !x_axis.is_active() { 1 } else if x_axis.value < 0.0 { 0 } else { 2 };
+    let row = if !y_axis.is_active() { 1 } else if y_axis.value < 0.0 { 0 } else { 2 };
+    1 + column + 3 * row
+}
+
+fn find_held_buttons(input: &ControllerState) -> String {
+    let buttons = [
+        ("A", input.a_button.is_pressed),
+        ("B", input.b_button.is_pressed),
+        ("X", input.x_button.is_pressed),
+        ("Y", input.y_button.is_pressed),
+        ("Z", input.z_button.is_pressed),
+        ("L", input.l_button.is_pressed),
+        ("R", input.r_button.is_pressed),
+        ("S", input.start_button.is_pressed),
+    ];
+    buttons.iter()
+        .filter(|(_, is_pressed)| *is_pressed)
+        .map(|(name, _)| *name)
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
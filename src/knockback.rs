@@ -0,0 +1,107 @@
+use crate::point_math::Point;
+use crate::vector_math::Vector;
+use crate::attack::Hitbox;
+use crate::stage::Bounds;
+
+// These follow Melee's formulas.
+pub const LAUNCH_SPEED_PER_KNOCKBACK: f64 = 0.03;
+pub const LAUNCH_SPEED_DECAY: f64 = 0.051;
+pub const HITSTUN_PER_KNOCKBACK: f64 = 0.4;
+pub const MAX_DI_ANGLE: f64 = 18.0;
+
+// The path a launched fighter would take if nothing got in the way. Points has the
+// starting position followed by one point per frame, and stops at the frame that
+// crosses the blast zone if there is one.
+pub struct Trajectory {
+    pub points: Vec<Point>,
+    pub hitstun_frames: u32,
+    pub blast_zone_crossing: Option<Point>,
+}
+
+// The percent is from before the hit.
+pub fn calculate_knockback(hitbox: &Hitbox, percent: f64, weight: f64) -> f64 {
+    let percent_after_hit = percent + hitbox.damage;
+    let scaled_damage = percent_after_hit / 10.0 + percent_after_hit * hitbox.damage / 20.0;
+    let weight_factor = 200.0 / (weight + 100.0);
+    (scaled_damage * weight_factor * 1.4 + 18.0) * hitbox.knockback_growth / 100.0 + hitbox.base_knockback
+}
+
+// Angles are in degrees like hitbox angles, and direction is 1 or -1 depending on which
+// way the attacker is facing.
+pub fn calculate_launch_velocity(knockback: f64, angle: f64, direction: f64) -> Vector {
+    let launch_speed = knockback * LAUNCH_SPEED_PER_KNOCKBACK;
+    let angle = angle.to_radians();
+    Vector {
+        x: direction * launch_speed * angle.cos(),
+        y: launch_speed * angle.sin(),
+    }
+}
+
+// Holding the stick perpendicular to the launch rotates it by up to 18 degrees toward
+// the stick. Holding along the launch does nothing.
+pub fn apply_di(launch_velocity: &Vector, stick: &Vector) -> Vector {
+    let launch_direction = launch_velocity.direction();
+    let perpendicular_amount = launch_direction.x * stick.y - launch_direction.y * stick.x;
+    launch_velocity.with_rotation(MAX_DI_ANGLE.to_radians() * perpendicular_amount.clamp(-1.0, 1.0))
+}
+
+// Launch velocity slows down every frame while the fighter falls under their own gravity
+// on top of it, the same as they would in the air normally. Stage collision is ignored.
+pub fn simulate_trajectory(
+    start: &Point,
+    launch_velocity: &Vector,
+    gravity: f64,
+    fall_velocity: f64,
+    blast_zone: &Bounds,
+    max_frames: u32,
+) -> Trajectory {
+    let mut position = start.clone();
+    let mut launch_velocity = launch_velocity.clone();
+    let hitstun_frames = (launch_velocity.magnitude() / LAUNCH_SPEED_PER_KNOCKBACK * HITSTUN_PER_KNOCKBACK).floor() as u32;
+    let mut fall_speed = 0.0;
+    let mut points = vec![position.clone()];
+
+    for _ in 0..max_frames {
+        let previous_position = position.clone();
+        position.x += launch_velocity.x;
+        position.y += launch_velocity.y + fall_speed;
+        points.push(position.clone());
+
+        if blast_zone.contains(&previous_position) && !blast_zone.contains(&position) {
+            return Trajectory {
+                points,
+                hitstun_frames,
+                blast_zone_crossing: Some(find_blast_zone_crossing(&previous_position, &position, blast_zone)),
+            };
+        }
+
+        let launch_speed = (launch_velocity.magnitude() - LAUNCH_SPEED_DECAY).max(0.0);
+        launch_velocity.set_magnitude(launch_speed);
+        fall_speed -= gravity.min(fall_velocity + fall_speed).max(0.0);
+    }
+
+    Trajectory {
+        points,
+        hitstun_frames,
+        blast_zone_crossing: None,
+    }
+}
+
+// Where the step from the inside point to the outside point first touches an edge.
+fn find_blast_zone_crossing(inside: &Point, outside: &Point, blast_zone: &Bounds) -> Point {
+    let find_fraction = |from: f64, to: f64, edge: f64| (edge - from) / (to - from);
+    let mut fraction: f64 = 1.0;
+    if outside.x <= blast_zone.left {
+        fraction = fraction.min(find_fraction(inside.x, outside.x, blast_zone.left));
+    }
+    if outside.x >= blast_zone.right {
+        fraction = fraction.min(find_fraction(inside.x, outside.x, blast_zone.right));
+    }
+    if outside.y <= blast_zone.bottom {
+        fraction = fraction.min(find_fraction(inside.y, outside.y, blast_zone.bottom));
+    }
+    if outside.y >= blast_zone.top {
+        fraction = fraction.min(find_fraction(inside.y, outside.y, blast_zone.top));
+    }
+    inside.lerp(outside, fraction)
+}
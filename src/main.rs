@@ -4,6 +4,9 @@ mod general_math;
 mod point_math;
 mod vector_math;
 mod line_math;
+mod collision_line;
+mod stage;
+mod match_rules;
 mod fixed_timestep;
 mod button;
 mod analog_axis;
@@ -11,6 +14,19 @@ mod controller_state;
 mod digital_input;
 mod fighter;
 mod ecb;
+mod attack;
+mod knockback;
+mod character;
+mod frame_data;
+mod hot_reload;
+mod input_history;
+mod canvas;
+mod piston_canvas;
+mod software_canvas;
+mod replay;
+mod rewind;
+mod training_mode;
+mod camera;
 mod fighting_game;
 mod fighting_game_renderer;
 
@@ -23,19 +39,133 @@ use std::time::Instant;
 
 use crate::digital_input::DigitalInput;
 use crate::controller_state::ControllerState;
-use crate::fighting_game::FightingGame;
+use crate::fighting_game::{FightingGame, MatchState};
+use crate::fighter::Fighter;
+use crate::stage::Stage;
+use crate::match_rules::MatchRules;
 use crate::fixed_timestep::FixedTimestep;
-use crate::fighting_game_renderer::FightingGameRenderer;
+use crate::hot_reload::HotReloader;
+use crate::rewind::Rewind;
+use crate::training_mode::TrainingMode;
+use crate::fighting_game_renderer::{FightingGameRenderer, RenderContext};
+use crate::piston_canvas::PistonCanvas;
+use crate::software_canvas::SoftwareCanvas;
 
 fn main() {
-    let mut window: PistonWindow = WindowSettings::new("Fighting Game", [800, 600]).build().unwrap();
+    // "--frame-data" checks movement against the recorded frame data without opening a
+    // window, and "--frame-data-record" records it again after an intended change.
+    if let Some(flag) = std::env::args().nth(1).filter(|arg| arg.starts_with("--frame-data")) {
+        let assets = find_folder::Search::ParentsThenKids(3, 3).for_folder("assets").unwrap();
+        match frame_data::run_folder(&assets, flag == "--frame-data-record") {
+            Ok(true) => std::process::exit(0),
+            Ok(false) => std::process::exit(1),
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            },
+        }
+    }
+
+    // "--export-replay <replay> <output>" plays a replay without opening a window and writes
+    // it to a GIF, or to a folder of PNGs if the output doesn't end in ".gif".
+    if std::env::args().nth(1).is_some_and(|arg| arg == "--export-replay") {
+        let (replay_path, output_path) = match (std::env::args().nth(2), std::env::args().nth(3)) {
+            (Some(replay_path), Some(output_path)) => (replay_path, output_path),
+            _ => {
+                eprintln!("Usage: --export-replay <replay> <output>");
+                eprintln!("Outputs ending in .gif are written as a GIF, anything else is a folder with a PNG for every frame.");
+                std::process::exit(1);
+            },
+        };
+        let assets = find_folder::Search::ParentsThenKids(3, 3).for_folder("assets").unwrap();
+        match replay::export(&assets, std::path::Path::new(&replay_path), std::path::Path::new(&output_path)) {
+            Ok(frame_count) => {
+                println!("Wrote {} frames to {}", frame_count, output_path);
+                std::process::exit(0);
+            },
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            },
+        }
+    }
+
+    // "--training" starts training mode against a dummy of the same character.
+    let is_training = std::env::args().any(|arg| arg == "--training");
+    // "--render-png=<path>" draws the first frame to an image without opening a window.
+    let possible_png_path = std::env::args().find_map(|arg| arg.strip_prefix("--render-png=").map(String::from));
+    let names: Vec<String> = std::env::args().skip(1).filter(|arg| !arg.starts_with("--")).collect();
+    // The stage can be picked by name on the command line, e.g. "battlefield".
+    let stage_name = names.first().cloned().unwrap_or("v_stage".to_string());
+    let assets = find_folder::Search::ParentsThenKids(3, 3).for_folder("assets").unwrap();
+    let stage_path = assets.join("stages").join(format!("{}.json", stage_name));
+    let stage = match Stage::from_file(&stage_path) {
+        Ok(stage) => stage,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        },
+    };
+
+    // Followed by the character, e.g. "marth".
+    let character_name = names.get(1).cloned().unwrap_or("fox".to_string());
+    let character_path = assets.join("characters").join(format!("{}.json", character_name));
+    let player_count = if is_training { 2 } else { 1 };
+    let mut fighters = Vec::new();
+    for _ in 0..player_count {
+        match Fighter::from_file(&character_path) {
+            Ok(fighter) => fighters.push(fighter),
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            },
+        }
+    }
+
+    let mut possible_training_mode = None;
+    let mut rules = MatchRules::default();
+    if is_training {
+        possible_training_mode = Some(TrainingMode::default());
+        rules.stock_count = 99;
+        rules.time_limit_seconds = None;
+    }
+
+    let mut fighting_game = FightingGame::new(rules, stage, fighters);
+    if is_training {
+        fighting_game.change_match_state(MatchState::Playing);
+    }
+    let mut hot_reloader = HotReloader::new(stage_path, vec![character_path; player_count]);
+    let mut rewind = Rewind::default();
+    let mut fighting_game_renderer = FightingGameRenderer::default();
+
+    let window_width = 800;
+    let window_height = 600;
+    if let Some(png_path) = possible_png_path {
+        let mut canvas = SoftwareCanvas::new(window_width, window_height);
+        fighting_game_renderer.update_camera(&fighting_game);
+        fighting_game_renderer.render(&mut canvas, &RenderContext {
+            game: &fighting_game,
+            possible_reload_error: &None,
+            possible_training_mode: possible_training_mode.as_ref(),
+            rewind: &rewind,
+            window_width: window_width as f64,
+            window_height: window_height as f64,
+            interpolation: 1.0,
+        });
+        if let Err(error) = canvas.save_png(std::path::Path::new(&png_path)) {
+            eprintln!("Could not write {}: {}", png_path, error);
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+
+    let title = format!("Fighting Game - {}", fighting_game.stage.name);
+    let mut window: PistonWindow = WindowSettings::new(title, [window_width, window_height]).build().unwrap();
     window.set_max_fps(300);
     window.set_ups(60);
-
+    let mut glyphs = window.load_font("C:/Windows/Fonts/consola.ttf").unwrap();
     let mut digital_input = DigitalInput::default();
     let mut controller_state = ControllerState::default();
-    let mut fighting_game = FightingGame::default();
-    let mut fighting_game_renderer = FightingGameRenderer::to_piston_window(&mut window);
     let mut fixed_timestep = FixedTimestep::with_fixed_fps(60.0);
 
     let mut time_previous = Instant::now();
@@ -43,26 +173,38 @@ fn main() {
         digital_input.update_states_with_piston_window_event(&event);
         digital_input.update_controller_state(&mut controller_state);
 
+        let window_size = window.size();
+        let window_width = window_size.width;
+        let window_height = window_size.height;
         let time_current = Instant::now();
         let delta = time_current - time_previous;
         time_previous = time_current;
-        fixed_timestep.update(delta, || fighting_game.update(&controller_state));
+        // Data files that changed on disk are picked up between frames.
+        hot_reloader.update(&mut fighting_game);
+        fixed_timestep.update(delta, || {
+            match &mut possible_training_mode {
+                Some(training_mode) => training_mode.update(&mut fighting_game, &mut rewind, &controller_state),
+                None => rewind.update(&mut fighting_game, &[&controller_state]),
+            }
+            fighting_game_renderer.input_history.record(&fighting_game.players[0].input);
+            fighting_game_renderer.update_camera(&fighting_game);
+        });
 
-        fighting_game_renderer.handle_mouse_pan_and_zoom(&event);
+        fighting_game_renderer.handle_mouse_pan_and_zoom(&event, window_width, window_height);
+        fighting_game_renderer.handle_key_toggles(&event);
 
-        let window_size = window.size();
-        let window_width = window_size.width;
-        let window_height = window_size.height;
         window.draw_2d(&event, |context, graphics, device| {
-            fighting_game_renderer.render(
-                context,
-                graphics,
-                device,
-                &fighting_game,
+            let mut canvas = PistonCanvas { context, graphics, glyphs: &mut glyphs };
+            fighting_game_renderer.render(&mut canvas, &RenderContext {
+                game: &fighting_game,
+                possible_reload_error: &hot_reloader.error,
+                possible_training_mode: possible_training_mode.as_ref(),
+                rewind: &rewind,
                 window_width,
                 window_height,
-                fixed_timestep.interpolation,
-            );
+                interpolation: fixed_timestep.interpolation,
+            });
+            glyphs.factory.encoder.flush(device);
         });
     }
 }
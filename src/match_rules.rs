@@ -0,0 +1,38 @@
+#[derive(Clone)]
+pub struct MatchRules {
+    pub stock_count: u32,
+    pub time_limit_seconds: Option<u32>,
+    pub items: bool,
+}
+
+impl MatchRules {
+    pub fn default() -> Self {
+        Self {
+            stock_count: 4,
+            time_limit_seconds: Some(8 * 60),
+            items: false,
+        }
+    }
+
+    pub fn time_limit_frames(&self) -> Option<u32> {
+        self.time_limit_seconds.map(|seconds| seconds * 60)
+    }
+}
+
+// A summary of how a match went, produced once it is over.
+#[derive(Clone)]
+pub struct MatchResults {
+    pub winner: Option<usize>,
+    pub was_quit: bool,
+    pub frames_played: u32,
+    pub players: Vec<PlayerResults>,
+}
+
+#[derive(Clone)]
+pub struct PlayerResults {
+    pub stocks_remaining: u32,
+    pub kos: u32,
+    pub falls: u32,
+    pub damage_dealt: f64,
+    pub l_cancel_rate: Option<f64>,
+}
@@ -0,0 +1,49 @@
+use piston_window::*;
+
+use crate::canvas::Canvas;
+
+// Draws to a piston window. The glyph cache still needs to be flushed to the device
+// after drawing.
+pub struct PistonCanvas<'a, 'b> {
+    pub context: Context,
+    pub graphics: &'a mut G2d<'b>,
+    pub glyphs: &'a mut Glyphs,
+}
+
+impl<'a, 'b> Canvas for PistonCanvas<'a, 'b> {
+    fn clear(&mut self, color: [f32; 4]) {
+        clear(color, self.graphics);
+    }
+
+    fn draw_line(&mut self, color: [f32; 4], radius: f64, line_points: [f64; 4]) {
+        line(color, radius, line_points, self.context.transform, self.graphics);
+    }
+
+    fn draw_polygon(&mut self, color: [f32; 4], points: &[[f64; 2]]) {
+        polygon(color, points, self.context.transform, self.graphics);
+    }
+
+    fn draw_rectangle(&mut self, color: [f32; 4], rectangle_points: [f64; 4]) {
+        rectangle(color, rectangle_points, self.context.transform, self.graphics);
+    }
+
+    fn draw_circle(&mut self, color: [f32; 4], center: [f64; 2], radius: f64, is_filled: bool) {
+        let circle = ellipse::circle(center[0], center[1], radius);
+        if is_filled {
+            Ellipse::new(color).draw(circle, &self.context.draw_state, self.context.transform, self.graphics);
+        }
+        else {
+            Ellipse::new_border(color, 0.5).draw(circle, &self.context.draw_state, self.context.transform, self.graphics);
+        }
+    }
+
+    fn draw_text(&mut self, text: &str, size: u32, color: [f32; 4], x: f64, y: f64) {
+        Text::new_color(color, size).draw(
+            text,
+            self.glyphs,
+            &self.context.draw_state,
+            self.context.transform.trans(x, y),
+            self.graphics,
+        ).unwrap();
+    }
+}
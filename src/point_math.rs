@@ -14,19 +14,6 @@ impl Point {
         }
     }
 
-    pub fn triplet_orientation(&self, q: &Self, r: &Self) -> Orientation {
-        let value = (q.y - self.y) * (r.x - q.x) - (q.x - self.x) * (r.y - q.y);
-        if value == 0.0 {
-            Orientation::Colinear
-        }
-        else if value > 0.0 {
-            Orientation::Clockwise
-        }
-        else {
-            Orientation::CounterClockwise
-        }
-    }
-
     pub fn lerp(&self, other_point: &Self, interpolation: f64) -> Self {
         Self {
             x: general_math::lerp(self.x, other_point.x, interpolation),
@@ -34,10 +21,3 @@ impl Point {
         }
     }
 }
-
-#[derive(PartialEq)]
-pub enum Orientation {
-    Colinear,
-    Clockwise,
-    CounterClockwise,
-}
@@ -0,0 +1,210 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use gif::SetParameter;
+use serde::Deserialize;
+
+use crate::controller_state::ControllerState;
+use crate::fighter::Fighter;
+use crate::fighting_game::{FightingGame, MatchState};
+use crate::fighting_game_renderer::{FightingGameRenderer, RenderContext};
+use crate::frame_data::InputFile;
+use crate::match_rules::MatchRules;
+use crate::rewind::Rewind;
+use crate::software_canvas::SoftwareCanvas;
+use crate::stage::Stage;
+
+// Plays a replay without a window and draws every frame, for sharing clips and showing
+// bugs. A replay names the stage and each player's character, and lists each player's
+// inputs the same way frame data checks do. Like the checks, the match skips the
+// countdown, and players keep playing with nothing held once their inputs run out.
+
+pub enum ReplayError {
+    Io(String, std::io::Error),
+    Parse(String, String, serde_json::Error),
+    Load(String, String),
+    Encode(String, String),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io(source, error) => write!(f, "Could not read or write {}: {}", source, error),
+            ReplayError::Parse(source, field, error) => write!(f, "Could not parse replay {} at {}: {}", source, field, error),
+            ReplayError::Load(source, error) => write!(f, "Could not set up replay {}: {}", source, error),
+            ReplayError::Encode(source, error) => write!(f, "Could not write {}: {}", source, error),
+        }
+    }
+}
+
+impl fmt::Debug for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// The same size the window opens at.
+const IMAGE_WIDTH: u32 = 800;
+const IMAGE_HEIGHT: u32 = 600;
+
+// GIF frame delays are in hundredths of a second, which 60 fps doesn't divide into.
+// Every frame is kept and the delays go 2, 2, 1 so each 3 frames take 5 hundredths.
+const GIF_FRAME_DELAYS: [u16; 3] = [2, 2, 1];
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+// Writes an animated GIF if the output ends in .gif, otherwise a folder of numbered PNGs.
+// Returns how many frames were played.
+pub fn export(assets: &Path, replay_path: &Path, output_path: &Path) -> Result<u32, ReplayError> {
+    let source = replay_path.display().to_string();
+    let text = fs::read_to_string(replay_path).map_err(|error| ReplayError::Io(source.clone(), error))?;
+    let deserializer = &mut serde_json::Deserializer::from_str(&text);
+    let replay_file: ReplayFile = serde_path_to_error::deserialize(deserializer)
+        .map_err(|error| ReplayError::Parse(
+            source.clone(),
+            error.path().to_string(),
+            error.into_inner(),
+        ))?;
+    if replay_file.players.is_empty() {
+        return Err(ReplayError::Load(source, String::from("A replay needs at least one player.")));
+    }
+
+    let stage_path = assets.join("stages").join(format!("{}.json", replay_file.stage));
+    let stage = Stage::from_file(&stage_path).map_err(|error| ReplayError::Load(source.clone(), error.to_string()))?;
+    let mut fighters = Vec::new();
+    for player_file in &replay_file.players {
+        let character_path = assets.join("characters").join(format!("{}.json", player_file.character));
+        let fighter = Fighter::from_file(&character_path).map_err(|error| ReplayError::Load(source.clone(), error.to_string()))?;
+        fighters.push(fighter);
+    }
+
+    let mut game = FightingGame::new(MatchRules::default(), stage, fighters);
+    game.change_match_state(MatchState::Playing);
+    let mut output = ExportOutput::new(output_path)?;
+    let mut renderer = FightingGameRenderer::default();
+    let rewind = Rewind::default();
+
+    // One held input for each frame, for each player.
+    let player_inputs: Vec<Vec<&InputFile>> = replay_file.players.iter()
+        .map(|player_file| player_file.inputs.iter()
+            .flat_map(|input_file| std::iter::repeat_n(input_file, input_file.frames as usize))
+            .collect())
+        .collect();
+    let frame_count = player_inputs.iter().map(|inputs| inputs.len()).max().unwrap_or(0);
+    let mut controller_states = vec![ControllerState::default(); player_inputs.len()];
+
+    for frame in 0..frame_count {
+        for (controller_state, inputs) in controller_states.iter_mut().zip(&player_inputs) {
+            match inputs.get(frame) {
+                Some(input_file) => input_file.apply_to(controller_state),
+                None => *controller_state = ControllerState::default(),
+            }
+        }
+        let inputs: Vec<&ControllerState> = controller_states.iter().collect();
+        game.update(&inputs);
+        renderer.input_history.record(&game.players[0].input);
+        renderer.update_camera(&game);
+
+        let mut canvas = SoftwareCanvas::new(IMAGE_WIDTH, IMAGE_HEIGHT);
+        renderer.render(&mut canvas, &RenderContext {
+            game: &game,
+            possible_reload_error: &None,
+            possible_training_mode: None,
+            rewind: &rewind,
+            window_width: IMAGE_WIDTH as f64,
+            window_height: IMAGE_HEIGHT as f64,
+            interpolation: 1.0,
+        });
+        output.write_frame(frame as u32, &canvas)?;
+    }
+
+    Ok(frame_count as u32)
+}
+
+enum ExportOutput {
+    Gif(String, gif::Encoder<BufWriter<File>>),
+    Images(PathBuf),
+}
+
+impl ExportOutput {
+    fn new(output_path: &Path) -> Result<Self, ReplayError> {
+        let output_source = output_path.display().to_string();
+        let is_gif = output_path.extension().is_some_and(|extension| extension == "gif");
+        if is_gif {
+            let file = File::create(output_path).map_err(|error| ReplayError::Io(output_source.clone(), error))?;
+            let mut encoder = gif::Encoder::new(BufWriter::new(file), IMAGE_WIDTH as u16, IMAGE_HEIGHT as u16, &[])
+                .map_err(|error| ReplayError::Io(output_source.clone(), error))?;
+            encoder.set(gif::Repeat::Infinite).map_err(|error| ReplayError::Io(output_source.clone(), error))?;
+            Ok(ExportOutput::Gif(output_source, encoder))
+        }
+        else {
+            fs::create_dir_all(output_path).map_err(|error| ReplayError::Io(output_source, error))?;
+            Ok(ExportOutput::Images(output_path.to_path_buf()))
+        }
+    }
+
+    fn write_frame(&mut self, frame: u32, canvas: &SoftwareCanvas) -> Result<(), ReplayError> {
+        match self {
+            ExportOutput::Gif(output_source, encoder) => {
+                let mut pixels = canvas.pixels.clone();
+                let mut gif_frame = gif::Frame::from_rgba_speed(canvas.width as u16, canvas.height as u16, &mut pixels, GIF_QUANTIZE_SPEED);
+                gif_frame.delay = GIF_FRAME_DELAYS[frame as usize % GIF_FRAME_DELAYS.len()];
+                encoder.write_frame(&gif_frame).map_err(|error| ReplayError::Encode(output_source.clone(), error.to_string()))
+            },
+            ExportOutput::Images(folder) => {
+                let path = folder.join(format!("frame_{:05}.png", frame));
+                canvas.save_png(&path).map_err(|error| ReplayError::Encode(path.display().to_string(), error.to_string()))
+            },
+        }
+    }
+}
+
+// Everything below mirrors the layout of the replay files.
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReplayFile {
+    stage: String,
+    players: Vec<ReplayPlayerFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReplayPlayerFile {
+    character: String,
+    inputs: Vec<InputFile>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    // Seven frames is two whole rounds of delays and one left over.
+    #[test]
+    fn gifs_keep_every_frame_at_60_fps() {
+        let folder = std::env::temp_dir().join(format!("replay_test_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let replay_path = folder.join("replay.json");
+        let gif_path = folder.join("replay.gif");
+        fs::write(&replay_path, r#"{
+            "stage": "final_destination",
+            "players": [{ "character": "fox", "inputs": [{ "frames": 7, "x": 1.0 }] }]
+        }"#).unwrap();
+
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        assert_eq!(export(&assets, &replay_path, &gif_path).unwrap(), 7);
+
+        let decoder = gif::Decoder::new(File::open(&gif_path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut delays = Vec::new();
+        while let Some(frame) = reader.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        assert_eq!(delays, vec![2, 2, 1, 2, 2, 1, 2]);
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
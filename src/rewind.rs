@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+
+use crate::controller_state::ControllerState;
+use crate::fighting_game::{FightingGame, MatchState};
+
+// Keeps the game from before each of the last few seconds of frames. While paused, the
+// player who paused can step backward with d-pad left and forward again with d-pad right,
+// and holding either one scrubs. Simulating a frame from an earlier point, by unpausing or
+// frame advancing, throws away the frames that were stepped back over.
+pub struct Rewind {
+    pub past: VecDeque<FightingGame>,
+    pub future: Vec<FightingGame>,
+    pub max_frames: usize,
+    pub scrub_delay_frames: u32,
+    pub scrub_held_frames: u32,
+}
+
+impl Rewind {
+    pub fn default() -> Self {
+        Self {
+            past: VecDeque::new(),
+            future: Vec::new(),
+            max_frames: 10 * 60,
+            scrub_delay_frames: 20,
+            scrub_held_frames: 0,
+        }
+    }
+
+    // Used in place of FightingGame::update.
+    pub fn update(&mut self, game: &mut FightingGame, inputs: &[&ControllerState]) {
+        let previous_game = game.clone();
+        game.update(inputs);
+
+        if game.match_frame != previous_game.match_frame {
+            self.record(previous_game);
+            return;
+        }
+        if !game.is_paused() {
+            self.scrub_held_frames = 0;
+            return;
+        }
+
+        // Stepping happens once when the button is pressed, then every frame once it has been held a while.
+        let input = &game.players[game.paused_by].input;
+        let is_scrubbing_back = input.d_left_button.is_pressed;
+        let is_scrubbing_forward = input.d_right_button.is_pressed && !is_scrubbing_back;
+        if is_scrubbing_back || is_scrubbing_forward {
+            self.scrub_held_frames += 1;
+        }
+        else {
+            self.scrub_held_frames = 0;
+        }
+        let should_step = self.scrub_held_frames == 1 || self.scrub_held_frames > self.scrub_delay_frames;
+
+        if should_step && is_scrubbing_back {
+            self.step_back(game);
+        }
+        else if should_step && is_scrubbing_forward {
+            self.step_forward(game);
+        }
+    }
+
+    // Should be given the game from before a frame was simulated.
+    pub fn record(&mut self, game: FightingGame) {
+        self.future.clear();
+        self.past.push_back(game);
+        if self.past.len() > self.max_frames {
+            self.past.pop_front();
+        }
+    }
+
+    pub fn step_back(&mut self, game: &mut FightingGame) -> bool {
+        match self.past.pop_back() {
+            Some(previous_game) => {
+                let current_game = std::mem::replace(game, previous_game);
+                Self::keep_controls(game, &current_game);
+                self.future.push(current_game);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn step_forward(&mut self, game: &mut FightingGame) -> bool {
+        match self.future.pop() {
+            Some(next_game) => {
+                let current_game = std::mem::replace(game, next_game);
+                Self::keep_controls(game, &current_game);
+                self.past.push_back(current_game);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn frames_back(&self) -> usize {
+        self.future.len()
+    }
+
+    // The stored games have the controller state from when they were recorded. The current
+    // controllers are kept so buttons that are held don't count as pressed again, and the
+    // game stays paused by whoever paused it.
+    fn keep_controls(game: &mut FightingGame, current_game: &FightingGame) {
+        for (player, current_player) in game.players.iter_mut().zip(&current_game.players) {
+            player.input = current_player.input.clone();
+        }
+        game.match_state = MatchState::Paused;
+        game.paused_by = current_game.paused_by;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::fighter::Fighter;
+    use crate::match_rules::MatchRules;
+    use crate::stage::Stage;
+
+    fn fox_game() -> FightingGame {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join("stages").join("final_destination.json");
+        let mut game = FightingGame::new(MatchRules::default(), Stage::from_file(&path).unwrap(), vec![Fighter::fox()]);
+        game.change_match_state(MatchState::Playing);
+        game
+    }
+
+    // Dashes, jumps, drifts back and attacks. Nothing is held on frame 39 or frame 59, so
+    // rewinding from 60 to 40 keeps the same controller history.
+    fn scripted_input(frame: u32) -> ControllerState {
+        let mut input = ControllerState::default();
+        match frame {
+            10..=24 => input.x_axis.value = 1.0,
+            25 => input.x_button.is_pressed = true,
+            26..=37 => input.x_axis.value = -0.5,
+            42 => input.a_button.is_pressed = true,
+            _ => (),
+        }
+        input
+    }
+
+    fn snapshot(game: &FightingGame) -> String {
+        let fighter = &game.players[0].fighter;
+        format!(
+            "frame {} stage {} {:?}({}) at ({}, {}) moving ({}, {})",
+            game.match_frame, game.stage.frame, fighter.state, fighter.state_frame,
+            fighter.position.x, fighter.position.y, fighter.velocity.x, fighter.velocity.y,
+        )
+    }
+
+    #[test]
+    fn rewinding_and_replaying_the_same_inputs_ends_up_the_same() {
+        let mut game = fox_game();
+        let mut rewind = Rewind::default();
+        for frame in 0..60 {
+            rewind.update(&mut game, &[&scripted_input(frame)]);
+        }
+        let original = snapshot(&game);
+
+        for _ in 0..20 {
+            assert!(rewind.step_back(&mut game));
+        }
+        assert_eq!(game.match_frame, 40);
+        assert_eq!(rewind.frames_back(), 20);
+
+        game.change_match_state(MatchState::Playing);
+        for frame in 40..60 {
+            rewind.update(&mut game, &[&scripted_input(frame)]);
+        }
+        assert_eq!(snapshot(&game), original);
+        assert_eq!(rewind.frames_back(), 0);
+    }
+
+    #[test]
+    fn only_the_last_max_frames_are_kept() {
+        let mut game = fox_game();
+        let mut rewind = Rewind::default();
+        rewind.max_frames = 30;
+        for frame in 0..60 {
+            rewind.update(&mut game, &[&scripted_input(frame)]);
+        }
+        assert_eq!(rewind.past.len(), 30);
+
+        while rewind.step_back(&mut game) {}
+        assert_eq!(rewind.frames_back(), 30);
+        assert_eq!(game.match_frame, 30);
+    }
+}
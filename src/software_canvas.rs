@@ -0,0 +1,379 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::canvas::Canvas;
+
+// Draws into an RGBA image in memory without a window or GPU, which can then be saved as
+// a PNG. Shapes are filled by checking the center of every pixel they might cover, so
+// there is no anti-aliasing and the same frame always comes out the same.
+pub struct SoftwareCanvas {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+// Text uses a built in 5x7 font so images don't depend on the fonts installed. Each
+// character is 5 columns from left to right, with the top row in the lowest bit.
+const FONT_WIDTH: usize = 5;
+const FONT_HEIGHT: usize = 7;
+const FONT_FIRST_CHARACTER: u8 = b' ';
+const FONT: [[u8; FONT_WIDTH]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5f, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], // #
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1c, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1c, 0x00], // )
+    [0x08, 0x2a, 0x1c, 0x2a, 0x08], // *
+    [0x08, 0x08, 0x3e, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], // 0
+    [0x00, 0x42, 0x7f, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4b, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7f, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1e], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3e], // @
+    [0x7e, 0x11, 0x11, 0x11, 0x7e], // A
+    [0x7f, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3e, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], // D
+    [0x7f, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7f, 0x09, 0x09, 0x01, 0x01], // F
+    [0x3e, 0x41, 0x41, 0x51, 0x32], // G
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], // H
+    [0x00, 0x41, 0x7f, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3f, 0x01], // J
+    [0x7f, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7f, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7f, 0x02, 0x04, 0x02, 0x7f], // M
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], // N
+    [0x3e, 0x41, 0x41, 0x41, 0x3e], // O
+    [0x7f, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3e, 0x41, 0x51, 0x21, 0x5e], // Q
+    [0x7f, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7f, 0x01, 0x01], // T
+    [0x3f, 0x40, 0x40, 0x40, 0x3f], // U
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], // V
+    [0x7f, 0x20, 0x18, 0x20, 0x7f], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x03, 0x04, 0x78, 0x04, 0x03], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7f, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7f, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7f, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7f], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7e, 0x09, 0x01, 0x02], // f
+    [0x08, 0x14, 0x54, 0x54, 0x3c], // g
+    [0x7f, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7d, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3d, 0x00], // j
+    [0x00, 0x7f, 0x10, 0x28, 0x44], // k
+    [0x00, 0x41, 0x7f, 0x40, 0x00], // l
+    [0x7c, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7c, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7c, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7c], // q
+    [0x7c, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3f, 0x44, 0x40, 0x20], // t
+    [0x3c, 0x40, 0x40, 0x20, 0x7c], // u
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], // v
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0c, 0x50, 0x50, 0x50, 0x3c], // y
+    [0x44, 0x64, 0x54, 0x4c, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7f, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x02, 0x01, 0x02, 0x04, 0x02], // ~
+];
+
+impl SoftwareCanvas {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; 4 * width as usize * height as usize],
+        }
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut png_writer = encoder.write_header()?;
+        png_writer.write_image_data(&self.pixels)
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), png::EncodingError> {
+        let file = File::create(path)?;
+        self.write_png(BufWriter::new(file))
+    }
+
+    // Blends the color over what is already there. The image itself stays opaque.
+    fn blend_pixel(&mut self, x: i64, y: i64, color: [f32; 4]) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let index = 4 * (y as usize * self.width as usize + x as usize);
+        let alpha = color[3].clamp(0.0, 1.0);
+        for (pixel, channel) in self.pixels[index..index + 3].iter_mut().zip(&color[..3]) {
+            let current = *pixel as f32 / 255.0;
+            let blended = current + (channel.clamp(0.0, 1.0) - current) * alpha;
+            *pixel = (blended * 255.0).round() as u8;
+        }
+        self.pixels[index + 3] = 255;
+    }
+
+    // Blends every pixel in the area whose center the function says is inside the shape,
+    // skipping anything off the image.
+    fn fill_where<F: Fn(f64, f64) -> bool>(&mut self, color: [f32; 4], left: f64, top: f64, right: f64, bottom: f64, is_inside: F) {
+        let start_x = left.floor().max(0.0) as i64;
+        let start_y = top.floor().max(0.0) as i64;
+        let end_x = right.ceil().min(self.width as f64) as i64;
+        let end_y = bottom.ceil().min(self.height as f64) as i64;
+        for y in start_y..end_y {
+            for x in start_x..end_x {
+                if is_inside(x as f64 + 0.5, y as f64 + 0.5) {
+                    self.blend_pixel(x, y, color);
+                }
+            }
+        }
+    }
+}
+
+impl Canvas for SoftwareCanvas {
+    fn clear(&mut self, color: [f32; 4]) {
+        let color_bytes = [
+            (color[0].clamp(0.0, 1.0) * 255.0).round() as u8,
+            (color[1].clamp(0.0, 1.0) * 255.0).round() as u8,
+            (color[2].clamp(0.0, 1.0) * 255.0).round() as u8,
+            255,
+        ];
+        for pixel in self.pixels.chunks_mut(4) {
+            pixel.copy_from_slice(&color_bytes);
+        }
+    }
+
+    // Lines are always at least a pixel thick so thin ones don't disappear.
+    fn draw_line(&mut self, color: [f32; 4], radius: f64, line: [f64; 4]) {
+        let radius = radius.max(0.5);
+        let [x1, y1, x2, y2] = line;
+        let length_squared = (x2 - x1).powi(2) + (y2 - y1).powi(2);
+        let distance_squared_to_line = |x: f64, y: f64| {
+            let along = if length_squared == 0.0 { 0.0 } else {
+                (((x - x1) * (x2 - x1) + (y - y1) * (y2 - y1)) / length_squared).clamp(0.0, 1.0)
+            };
+            (x - (x1 + along * (x2 - x1))).powi(2) + (y - (y1 + along * (y2 - y1))).powi(2)
+        };
+        self.fill_where(
+            color,
+            x1.min(x2) - radius,
+            y1.min(y2) - radius,
+            x1.max(x2) + radius,
+            y1.max(y2) + radius,
+            |x, y| distance_squared_to_line(x, y) <= radius * radius,
+        );
+    }
+
+    fn draw_polygon(&mut self, color: [f32; 4], points: &[[f64; 2]]) {
+        if points.len() < 3 {
+            return;
+        }
+        let left = points.iter().map(|point| point[0]).fold(f64::INFINITY, f64::min);
+        let right = points.iter().map(|point| point[0]).fold(f64::NEG_INFINITY, f64::max);
+        let top = points.iter().map(|point| point[1]).fold(f64::INFINITY, f64::min);
+        let bottom = points.iter().map(|point| point[1]).fold(f64::NEG_INFINITY, f64::max);
+
+        // Counts how many edges a ray going right from the point crosses.
+        let is_inside = |x: f64, y: f64| {
+            let mut is_inside = false;
+            let mut previous_point = points[points.len() - 1];
+            for point in points {
+                if (point[1] > y) != (previous_point[1] > y) {
+                    let crossing_x = point[0] + (y - point[1]) / (previous_point[1] - point[1]) * (previous_point[0] - point[0]);
+                    if x < crossing_x {
+                        is_inside = !is_inside;
+                    }
+                }
+                previous_point = *point;
+            }
+            is_inside
+        };
+        self.fill_where(color, left, top, right, bottom, is_inside);
+    }
+
+    fn draw_rectangle(&mut self, color: [f32; 4], rectangle: [f64; 4]) {
+        let [x, y, width, height] = rectangle;
+        self.fill_where(color, x, y, x + width, y + height, |_, _| true);
+    }
+
+    fn draw_circle(&mut self, color: [f32; 4], center: [f64; 2], radius: f64, is_filled: bool) {
+        let border_radius = 0.5;
+        let outer_radius = if is_filled { radius } else { radius + border_radius };
+        let inner_radius = if is_filled { 0.0 } else { (radius - border_radius).max(0.0) };
+        let is_inside = |x: f64, y: f64| {
+            let distance_squared = (x - center[0]).powi(2) + (y - center[1]).powi(2);
+            distance_squared <= outer_radius * outer_radius
+            && (is_filled || distance_squared >= inner_radius * inner_radius)
+        };
+        self.fill_where(
+            color,
+            center[0] - outer_radius,
+            center[1] - outer_radius,
+            center[0] + outer_radius,
+            center[1] + outer_radius,
+            is_inside,
+        );
+    }
+
+    // The font is scaled so characters take up about as much room as they do in the window.
+    fn draw_text(&mut self, text: &str, size: u32, color: [f32; 4], x: f64, y: f64) {
+        let scale = size as f64 / 10.0;
+        let character_advance = (FONT_WIDTH + 1) as f64 * scale;
+        let top = y - FONT_HEIGHT as f64 * scale;
+        for (character_index, character) in text.chars().enumerate() {
+            let glyph = match (character as u32).checked_sub(FONT_FIRST_CHARACTER as u32) {
+                Some(glyph_index) if (glyph_index as usize) < FONT.len() => &FONT[glyph_index as usize],
+                _ => &FONT[(b'?' - FONT_FIRST_CHARACTER) as usize],
+            };
+            let left = x + character_advance * character_index as f64;
+            self.fill_where(
+                color,
+                left,
+                top,
+                left + FONT_WIDTH as f64 * scale,
+                top + FONT_HEIGHT as f64 * scale,
+                |pixel_x, pixel_y| {
+                    let column = ((pixel_x - left) / scale) as usize;
+                    let row = ((pixel_y - top) / scale) as usize;
+                    column < FONT_WIDTH && row < FONT_HEIGHT && glyph[column] & (1 << row) != 0
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+    // A black canvas, so anything drawn in white shows up in the red channel.
+    fn black_canvas(width: u32, height: u32) -> SoftwareCanvas {
+        let mut canvas = SoftwareCanvas::new(width, height);
+        canvas.clear([0.0, 0.0, 0.0, 1.0]);
+        canvas
+    }
+
+    fn is_drawn(canvas: &SoftwareCanvas, x: u32, y: u32) -> bool {
+        canvas.pixels[4 * (y * canvas.width + x) as usize] != 0
+    }
+
+    fn drawn_count(canvas: &SoftwareCanvas) -> usize {
+        canvas.pixels.chunks(4).filter(|pixel| pixel[0] != 0).count()
+    }
+
+    #[test]
+    fn rectangles_fill_the_pixels_they_cover() {
+        let mut canvas = black_canvas(10, 10);
+        canvas.draw_rectangle(WHITE, [2.0, 3.0, 4.0, 2.0]);
+        assert_eq!(drawn_count(&canvas), 8);
+        assert!(is_drawn(&canvas, 2, 3) && is_drawn(&canvas, 5, 4));
+        assert!(!is_drawn(&canvas, 1, 3) && !is_drawn(&canvas, 6, 4) && !is_drawn(&canvas, 2, 5));
+    }
+
+    // Pixel centers within 3 of the center, which is 32 pixels.
+    #[test]
+    fn circles_fill_by_pixel_center() {
+        let mut canvas = black_canvas(20, 20);
+        canvas.draw_circle(WHITE, [10.0, 10.0], 3.0, true);
+        assert_eq!(drawn_count(&canvas), 32);
+        assert!(is_drawn(&canvas, 7, 10) && is_drawn(&canvas, 10, 12));
+        assert!(!is_drawn(&canvas, 6, 10) && !is_drawn(&canvas, 10, 13));
+
+        let mut canvas = black_canvas(20, 20);
+        canvas.draw_circle(WHITE, [10.0, 10.0], 3.0, false);
+        assert!(is_drawn(&canvas, 7, 10));
+        assert!(!is_drawn(&canvas, 10, 10));
+    }
+
+    // A thin line through pixel centers covers just that row, end cap included.
+    #[test]
+    fn lines_cover_pixels_near_them() {
+        let mut canvas = black_canvas(10, 10);
+        canvas.draw_line(WHITE, 0.0, [2.5, 5.5, 7.5, 5.5]);
+        assert_eq!(drawn_count(&canvas), 6);
+        assert!((2..8).all(|x| is_drawn(&canvas, x, 5)));
+
+        let mut canvas = black_canvas(10, 10);
+        canvas.draw_line(WHITE, 1.0, [0.5, 0.5, 9.5, 9.5]);
+        assert!((0..10).all(|x| is_drawn(&canvas, x, x)));
+        assert!(!is_drawn(&canvas, 9, 0) && !is_drawn(&canvas, 0, 9));
+    }
+
+    #[test]
+    fn shapes_are_clipped_to_the_image() {
+        let mut canvas = black_canvas(10, 10);
+        canvas.draw_rectangle(WHITE, [-5.0, -5.0, 8.0, 8.0]);
+        assert_eq!(drawn_count(&canvas), 9);
+
+        let mut canvas = black_canvas(10, 10);
+        canvas.draw_circle(WHITE, [10.0, 5.0], 2.0, true);
+        assert!(is_drawn(&canvas, 9, 5));
+        assert!((0..10).all(|y| (0..8).all(|x| !is_drawn(&canvas, x, y))));
+
+        let mut canvas = black_canvas(10, 10);
+        canvas.draw_line(WHITE, 0.0, [-20.0, 5.5, 30.0, 5.5]);
+        assert_eq!(drawn_count(&canvas), 10);
+
+        let mut canvas = black_canvas(10, 10);
+        canvas.draw_circle(WHITE, [-50.0, -50.0], 5.0, true);
+        canvas.draw_polygon(WHITE, &[[20.0, 20.0], [30.0, 20.0], [25.0, 30.0]]);
+        assert_eq!(drawn_count(&canvas), 0);
+    }
+
+    #[test]
+    fn polygons_fill_their_inside() {
+        let mut canvas = black_canvas(10, 10);
+        canvas.draw_polygon(WHITE, &[[0.0, 0.0], [10.0, 0.0], [0.0, 10.0]]);
+        assert!(is_drawn(&canvas, 0, 0) && is_drawn(&canvas, 8, 0) && is_drawn(&canvas, 0, 8));
+        assert!(!is_drawn(&canvas, 9, 9) && !is_drawn(&canvas, 5, 5));
+    }
+
+    #[test]
+    fn colors_blend_by_alpha() {
+        let mut canvas = black_canvas(1, 1);
+        canvas.draw_rectangle([1.0, 1.0, 1.0, 0.5], [0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(canvas.pixels, vec![128, 128, 128, 255]);
+    }
+}
@@ -0,0 +1,460 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::point_math::Point;
+use crate::vector_math::Vector;
+use crate::line_math::LineSegment;
+use crate::collision_line::{CollisionLine, CollisionPolyLine, CollisionPath};
+
+#[derive(Clone)]
+pub struct Stage {
+    pub name: String,
+    pub collision_poly_lines: Vec<CollisionPolyLine>,
+    pub spawn_points: Vec<SpawnPoint>,
+    pub respawn_points: Vec<SpawnPoint>,
+    pub ledges: Vec<Ledge>,
+    pub blast_zone: Bounds,
+    pub camera_bounds: Bounds,
+    pub frame: u32,
+}
+
+// The side says which end of the ground line the ledge hangs off.
+#[derive(Clone)]
+pub struct Ledge {
+    pub position: Point,
+    pub side: LedgeSide,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+pub enum LedgeSide {
+    Left,
+    Right,
+}
+
+#[derive(Clone)]
+pub struct SpawnPoint {
+    pub position: Point,
+    pub is_facing_right: bool,
+}
+
+#[derive(Clone)]
+pub struct Bounds {
+    pub left: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub top: f64,
+}
+
+impl Bounds {
+    pub fn contains(&self, point: &Point) -> bool {
+        point.x > self.left && point.x < self.right
+        && point.y > self.bottom && point.y < self.top
+    }
+
+    pub fn contains_bounds(&self, other: &Self) -> bool {
+        other.left >= self.left && other.right <= self.right
+        && other.bottom >= self.bottom && other.top <= self.top
+    }
+}
+
+pub enum StageError {
+    Io(String, std::io::Error),
+    Parse(String, String, serde_json::Error),
+    Invalid(String, Vec<String>),
+}
+
+impl fmt::Display for StageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StageError::Io(source, error) => write!(f, "Could not read stage {}: {}", source, error),
+            StageError::Parse(source, field, error) => write!(f, "Could not parse stage {} at {}: {}", source, field, error),
+            StageError::Invalid(source, problems) => {
+                write!(f, "Stage {} is invalid:", source)?;
+                for problem in problems {
+                    write!(f, "\n    {}", problem)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl fmt::Debug for StageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Stage {
+    pub fn from_file(path: &Path) -> Result<Self, StageError> {
+        let source = path.display().to_string();
+        let text = fs::read_to_string(path).map_err(|error| StageError::Io(source.clone(), error))?;
+        Self::from_json(&text, &source)
+    }
+
+    // The source is only used to say where errors came from.
+    pub fn from_json(text: &str, source: &str) -> Result<Self, StageError> {
+        let deserializer = &mut serde_json::Deserializer::from_str(text);
+        let stage_file: StageFile = serde_path_to_error::deserialize(deserializer)
+            .map_err(|error| StageError::Parse(
+                source.to_string(),
+                error.path().to_string(),
+                error.into_inner(),
+            ))?;
+
+        let problems = stage_file.validate();
+        if !problems.is_empty() {
+            return Err(StageError::Invalid(source.to_string(), problems));
+        }
+
+        Ok(stage_file.into_stage())
+    }
+
+    // Moves any poly lines that follow a path.
+    pub fn update(&mut self) {
+        self.frame += 1;
+        for poly_line in &mut self.collision_poly_lines {
+            poly_line.move_to_frame(self.frame);
+        }
+    }
+
+    // Puts moving poly lines where they would be on the given frame without
+    // them counting as having moved there.
+    pub fn set_frame(&mut self, frame: u32) {
+        self.frame = frame;
+        for poly_line in &mut self.collision_poly_lines {
+            poly_line.move_to_frame(frame);
+            poly_line.movement = Vector::default();
+        }
+    }
+}
+
+// Everything below mirrors the layout of the stage files.
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StageFile {
+    name: String,
+    collision_poly_lines: Vec<PolyLineFile>,
+    ledges: Vec<LedgeFile>,
+    spawn_points: Vec<SpawnPointFile>,
+    respawn_points: Vec<SpawnPointFile>,
+    blast_zone: BoundsFile,
+    camera_bounds: BoundsFile,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PointFile {
+    x: f64,
+    y: f64,
+}
+
+// Each line type covers the segment that starts at the point with the same index.
+// Points are where the poly line is without any offset from its path.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolyLineFile {
+    points: Vec<PointFile>,
+    line_types: Vec<LineType>,
+    #[serde(default)]
+    path: Option<PathFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PathFile {
+    waypoints: Vec<PointFile>,
+    frames: Vec<u32>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+enum LineType {
+    Ground,
+    Wall,
+    Ceiling,
+    Platform,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LedgeFile {
+    position: PointFile,
+    side: LedgeSide,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpawnPointFile {
+    position: PointFile,
+    is_facing_right: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BoundsFile {
+    left: f64,
+    right: f64,
+    bottom: f64,
+    top: f64,
+}
+
+impl PointFile {
+    fn to_point(&self) -> Point {
+        Point {
+            x: self.x,
+            y: self.y,
+        }
+    }
+}
+
+impl BoundsFile {
+    fn to_bounds(&self) -> Bounds {
+        Bounds {
+            left: self.left,
+            right: self.right,
+            bottom: self.bottom,
+            top: self.top,
+        }
+    }
+}
+
+impl PathFile {
+    fn to_path(&self) -> CollisionPath {
+        CollisionPath {
+            waypoints: self.waypoints.iter()
+                .map(|waypoint| Vector {
+                    x: waypoint.x,
+                    y: waypoint.y,
+                })
+                .collect(),
+            frames: self.frames.clone(),
+        }
+    }
+}
+
+impl PolyLineFile {
+    fn segments(&self) -> Vec<LineSegment> {
+        self.points.windows(2)
+            .map(|pair| LineSegment {
+                point_a: pair[0].to_point(),
+                point_b: pair[1].to_point(),
+            })
+            .collect()
+    }
+}
+
+impl StageFile {
+    // Collects every problem instead of stopping at the first one so a stage
+    // can be fixed in one go.
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        for (poly_line_index, poly_line) in self.collision_poly_lines.iter().enumerate() {
+            if poly_line.points.len() < 2 {
+                problems.push(format!("Poly line {} needs at least 2 points.", poly_line_index));
+                continue;
+            }
+            if poly_line.line_types.len() != poly_line.points.len() - 1 {
+                problems.push(format!(
+                    "Poly line {} has {} points so it needs {} line types, but it has {}.",
+                    poly_line_index,
+                    poly_line.points.len(),
+                    poly_line.points.len() - 1,
+                    poly_line.line_types.len(),
+                ));
+                continue;
+            }
+
+            if let Some(path) = &poly_line.path {
+                if path.waypoints.is_empty() {
+                    problems.push(format!("Poly line {} has a path with no waypoints.", poly_line_index));
+                }
+                if path.frames.len() != path.waypoints.len() {
+                    problems.push(format!(
+                        "Poly line {} has a path with {} waypoints so it needs {} frame counts, but it has {}.",
+                        poly_line_index,
+                        path.waypoints.len(),
+                        path.waypoints.len(),
+                        path.frames.len(),
+                    ));
+                }
+                if path.frames.contains(&0) {
+                    problems.push(format!("Poly line {} has a path that takes 0 frames to reach a waypoint.", poly_line_index));
+                }
+            }
+
+            // The collision code decides how a line behaves by which way it faces,
+            // so the declared type has to agree with the geometry.
+            for (line_index, segment) in poly_line.segments().iter().enumerate() {
+                let line_type = poly_line.line_types[line_index];
+                if segment.length() <= 0.0 {
+                    problems.push(format!("Poly line {} line {} has no length.", poly_line_index, line_index));
+                    continue;
+                }
+
+                let collision_line = CollisionLine::solid(segment.clone());
+                let geometry_type = if collision_line.is_ground() {
+                    LineType::Ground
+                }
+                else if collision_line.is_ceiling() {
+                    LineType::Ceiling
+                }
+                else {
+                    LineType::Wall
+                };
+
+                let is_matching = match line_type {
+                    LineType::Platform => geometry_type == LineType::Ground,
+                    _ => geometry_type == line_type,
+                };
+                if !is_matching {
+                    problems.push(format!(
+                        "Poly line {} line {} is declared {:?} but faces like {:?}.",
+                        poly_line_index,
+                        line_index,
+                        line_type,
+                        geometry_type,
+                    ));
+                }
+            }
+        }
+
+        // Ledges have to sit on the end of a solid ground line. Ledges are only checked
+        // against the collision lines for now, nothing grabs them yet.
+        for (ledge_index, ledge) in self.ledges.iter().enumerate() {
+            let tolerance = 0.01;
+            let position = ledge.position.to_point();
+            let is_on_ground_end = self.collision_poly_lines.iter()
+                .filter(|poly_line| poly_line.line_types.len() + 1 == poly_line.points.len())
+                .any(|poly_line| {
+                    poly_line.segments().iter().zip(&poly_line.line_types).any(|(segment, line_type)| {
+                        let end = match ledge.side {
+                            LedgeSide::Left => segment.left_point(),
+                            LedgeSide::Right => segment.right_point(),
+                        };
+                        *line_type == LineType::Ground
+                        && (end.x - position.x).abs() <= tolerance
+                        && (end.y - position.y).abs() <= tolerance
+                    })
+                });
+            if !is_on_ground_end {
+                problems.push(format!(
+                    "Ledge {} at ({}, {}) is not on the {:?} end of a ground line.",
+                    ledge_index,
+                    position.x,
+                    position.y,
+                    ledge.side,
+                ));
+            }
+        }
+
+        let blast_zone = self.blast_zone.to_bounds();
+        let camera_bounds = self.camera_bounds.to_bounds();
+        if blast_zone.left >= blast_zone.right || blast_zone.bottom >= blast_zone.top {
+            problems.push("The blast zone has no area.".to_string());
+        }
+        if camera_bounds.left >= camera_bounds.right || camera_bounds.bottom >= camera_bounds.top {
+            problems.push("The camera bounds have no area.".to_string());
+        }
+        if !blast_zone.contains_bounds(&camera_bounds) {
+            problems.push("The camera bounds have to be inside the blast zone.".to_string());
+        }
+
+        if self.spawn_points.is_empty() {
+            problems.push("There needs to be at least 1 spawn point.".to_string());
+        }
+        if self.respawn_points.is_empty() {
+            problems.push("There needs to be at least 1 respawn point.".to_string());
+        }
+        for (name, spawn_points) in &[("Spawn", &self.spawn_points), ("Respawn", &self.respawn_points)] {
+            for (spawn_index, spawn_point) in spawn_points.iter().enumerate() {
+                if !blast_zone.contains(&spawn_point.position.to_point()) {
+                    problems.push(format!("{} point {} is outside of the blast zone.", name, spawn_index));
+                }
+            }
+        }
+
+        problems
+    }
+
+    fn into_stage(self) -> Stage {
+        let collision_poly_lines = self.collision_poly_lines.iter()
+            .map(|poly_line| {
+                let mut collision_poly_line = CollisionPolyLine::from_lines(
+                    poly_line.segments().into_iter().zip(&poly_line.line_types)
+                        .map(|(segment, line_type)| match line_type {
+                            LineType::Platform => CollisionLine::pass_through(segment),
+                            _ => CollisionLine::solid(segment),
+                        })
+                        .collect()
+                );
+
+                // Start moving lines at the beginning of their path.
+                collision_poly_line.path = poly_line.path.as_ref().map(PathFile::to_path);
+                collision_poly_line.move_to_frame(0);
+                collision_poly_line.movement = Vector::default();
+                collision_poly_line
+            })
+            .collect();
+
+        let to_spawn_point = |spawn_point: &SpawnPointFile| SpawnPoint {
+            position: spawn_point.position.to_point(),
+            is_facing_right: spawn_point.is_facing_right,
+        };
+
+        Stage {
+            collision_poly_lines,
+            spawn_points: self.spawn_points.iter().map(to_spawn_point).collect(),
+            respawn_points: self.respawn_points.iter().map(to_spawn_point).collect(),
+            ledges: self.ledges.iter()
+                .map(|ledge| Ledge {
+                    position: ledge.position.to_point(),
+                    side: ledge.side,
+                })
+                .collect(),
+            blast_zone: self.blast_zone.to_bounds(),
+            camera_bounds: self.camera_bounds.to_bounds(),
+            frame: 0,
+            name: self.name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn final_destination_text() -> String {
+        fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join("stages").join("final_destination.json")).unwrap()
+    }
+
+    #[test]
+    fn ledges_are_kept_on_the_stage() {
+        let stage = Stage::from_json(&final_destination_text(), "final_destination.json").unwrap();
+        assert_eq!(stage.ledges.len(), 2);
+        assert_eq!((stage.ledges[0].position.x, stage.ledges[0].position.y, stage.ledges[0].side), (-56.0, -3.5, LedgeSide::Left));
+        assert_eq!((stage.ledges[1].position.x, stage.ledges[1].position.y, stage.ledges[1].side), (56.0, -3.5, LedgeSide::Right));
+    }
+
+    // Moves the right ledge off the end of the ground line.
+    #[test]
+    fn ledges_off_the_collision_lines_are_rejected() {
+        let text = final_destination_text().replacen(r#""x": 56.0, "y": -3.5 },
+            "side": "Right""#, r#""x": 50.0, "y": -3.5 },
+            "side": "Right""#, 1);
+        let folder = std::env::temp_dir().join(format!("stage_test_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let path = folder.join("bad_ledge.json");
+        fs::write(&path, text).unwrap();
+
+        let error = Stage::from_file(&path).err().expect("the ledge should be rejected").to_string();
+        assert!(error.contains(&path.display().to_string()), "{}", error);
+        assert!(error.contains("Ledge 1 at (50, -3.5) is not on the Right end of a ground line."), "{}", error);
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,416 @@
+use crate::controller_state::ControllerState;
+use crate::fighter::{Fighter, FighterState};
+use crate::fighting_game::{FightingGame, MatchState};
+use crate::rewind::Rewind;
+use crate::attack::Hitbox;
+use crate::vector_math::Vector;
+use crate::knockback::{self, Trajectory};
+
+// Wraps a game to add training features. Player 1 controls their fighter as usual and
+// the second fighter is a dummy driven by the chosen behaviors. The d-pad does the rest:
+//
+//     Down:   Save state.
+//     Up:     Load state.
+//     Right:  Change the dummy behavior. Hold L to change DI instead, R to change
+//             the tech option, or Z to change the hitbox the knockback preview uses.
+//     Left:   Add 10% to the dummy's percent.
+//
+// L, R and Z don't reach player 1's fighter from the moment they are used with right
+// until they are let go, so changing settings doesn't shield or air dodge. While paused,
+// left and right step through the rewind history instead.
+pub struct TrainingMode {
+    pub player_index: usize,
+    pub dummy_index: usize,
+    pub dummy_behavior: DummyBehavior,
+    pub di_behavior: DIBehavior,
+    pub tech_behavior: TechBehavior,
+    pub should_tech: bool,
+    pub is_changing_settings: bool,
+    pub input: ControllerState,
+    pub player_input: ControllerState,
+    pub dummy_input: ControllerState,
+    pub savestate: Option<FightingGame>,
+    pub random_state: u64,
+    pub random_di_direction: f64,
+    pub max_dummy_percent: f64,
+    pub preview_attack_index: usize,
+    pub preview_hitbox_index: usize,
+}
+
+// Where the dummy would go if the preview hitbox hit them with one of the DI options.
+pub struct KnockbackPreview {
+    pub di_behavior: DIBehavior,
+    pub trajectory: Trajectory,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DummyBehavior {
+    Stand,
+    Crouch,
+    Jump,
+    Shield,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DIBehavior {
+    None,
+    In,
+    Out,
+    Random,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TechBehavior {
+    None,
+    InPlace,
+    Random,
+}
+
+impl TrainingMode {
+    pub fn default() -> Self {
+        Self {
+            player_index: 0,
+            dummy_index: 1,
+            dummy_behavior: DummyBehavior::Stand,
+            di_behavior: DIBehavior::None,
+            tech_behavior: TechBehavior::None,
+            should_tech: false,
+            is_changing_settings: false,
+            input: ControllerState::default(),
+            player_input: ControllerState::default(),
+            dummy_input: ControllerState::default(),
+            savestate: None,
+            random_state: 0x2545_F491_4F6C_DD1D,
+            random_di_direction: 0.0,
+            max_dummy_percent: 300.0,
+            preview_attack_index: 0,
+            preview_hitbox_index: 0,
+        }
+    }
+
+    // Used in place of Rewind::update.
+    pub fn update(&mut self, game: &mut FightingGame, rewind: &mut Rewind, input: &ControllerState) {
+        self.input.update();
+        self.input.copy_inputs(input);
+
+        // The frame is still played after saving or loading so no input is lost.
+        if self.input.d_down_button.just_pressed() {
+            self.savestate = Some(game.clone());
+        }
+        else if self.input.d_up_button.just_pressed() {
+            // Loading can be undone by stepping back.
+            if let Some(savestate) = &self.savestate {
+                let current_game = std::mem::replace(game, savestate.clone());
+                for (player, current_player) in game.players.iter_mut().zip(&current_game.players) {
+                    player.input = current_player.input.clone();
+                }
+                if current_game.is_paused() {
+                    game.match_state = MatchState::Paused;
+                    game.paused_by = current_game.paused_by;
+                }
+                rewind.record(current_game);
+            }
+        }
+        let modifier_is_pressed = self.input.l_button.is_pressed
+                               || self.input.r_button.is_pressed
+                               || self.input.z_button.is_pressed;
+        if !game.is_paused() && self.input.d_right_button.is_pressed && modifier_is_pressed {
+            self.is_changing_settings = true;
+        }
+        else if !modifier_is_pressed {
+            self.is_changing_settings = false;
+        }
+
+        if !game.is_paused() && self.input.d_right_button.just_pressed() {
+            if self.input.z_button.is_pressed {
+                self.cycle_preview_hitbox(game);
+            }
+            else {
+                self.cycle_setting();
+            }
+        }
+        if !game.is_paused() && self.input.d_left_button.just_pressed() {
+            if let Some(dummy) = game.players.get_mut(self.dummy_index) {
+                dummy.fighter.percent += 10.0;
+                if dummy.fighter.percent > self.max_dummy_percent {
+                    dummy.fighter.percent = 0.0;
+                }
+            }
+        }
+
+        if let Some(dummy) = game.players.get(self.dummy_index) {
+            let opponent_x = game.players[self.player_index].fighter.position.x;
+            self.update_dummy_input(&dummy.fighter, opponent_x);
+        }
+
+        self.player_input = input.clone();
+        if self.is_changing_settings {
+            self.player_input.l_button.is_pressed = false;
+            self.player_input.r_button.is_pressed = false;
+            self.player_input.z_button.is_pressed = false;
+        }
+
+        let mut inputs = vec![&self.dummy_input; game.players.len()];
+        inputs[self.player_index] = &self.player_input;
+        rewind.update(game, &inputs);
+    }
+
+    fn cycle_setting(&mut self) {
+        if self.input.l_button.is_pressed {
+            self.di_behavior = match self.di_behavior {
+                DIBehavior::None => DIBehavior::In,
+                DIBehavior::In => DIBehavior::Out,
+                DIBehavior::Out => DIBehavior::Random,
+                DIBehavior::Random => DIBehavior::None,
+            };
+        }
+        else if self.input.r_button.is_pressed {
+            self.tech_behavior = match self.tech_behavior {
+                TechBehavior::None => TechBehavior::InPlace,
+                TechBehavior::InPlace => TechBehavior::Random,
+                TechBehavior::Random => TechBehavior::None,
+            };
+        }
+        else {
+            self.dummy_behavior = match self.dummy_behavior {
+                DummyBehavior::Stand => DummyBehavior::Crouch,
+                DummyBehavior::Crouch => DummyBehavior::Jump,
+                DummyBehavior::Jump => DummyBehavior::Shield,
+                DummyBehavior::Shield => DummyBehavior::Stand,
+            };
+        }
+    }
+
+    // Goes through every hitbox of every attack the player has.
+    fn cycle_preview_hitbox(&mut self, game: &FightingGame) {
+        let attacks = &game.players[self.player_index].fighter.attacks;
+        if attacks.is_empty() {
+            return;
+        }
+        self.preview_attack_index %= attacks.len();
+        self.preview_hitbox_index += 1;
+        if self.preview_hitbox_index >= attacks[self.preview_attack_index].hitboxes.len() {
+            self.preview_hitbox_index = 0;
+            self.preview_attack_index = (self.preview_attack_index + 1) % attacks.len();
+        }
+    }
+
+    // A hitbox the player has out right now is used over the one that was picked.
+    pub fn preview_hitbox<'a>(&self, game: &'a FightingGame) -> Option<&'a Hitbox> {
+        let fighter = &game.players[self.player_index].fighter;
+        if let Some(hitbox) = fighter.active_hitboxes().first().copied() {
+            return Some(hitbox);
+        }
+        fighter.attacks.get(self.preview_attack_index)
+            .and_then(|attack| attack.hitboxes.get(self.preview_hitbox_index))
+    }
+
+    // The length and landing lag of the attack the picked hitbox belongs to.
+    pub fn preview_timing(&self, game: &FightingGame) -> Option<String> {
+        let fighter = &game.players[self.player_index].fighter;
+        fighter.attacks.get(self.preview_attack_index)
+            .map(|attack| format!(
+                "{} frames, lag {} ({} l-cancelled)",
+                attack.total_frames,
+                attack.landing_lag,
+                attack.l_cancel_landing_lag,
+            ))
+    }
+
+    pub fn preview_name(&self, game: &FightingGame) -> Option<String> {
+        let fighter = &game.players[self.player_index].fighter;
+        fighter.attacks.get(self.preview_attack_index)
+            .map(|attack| format!("{} hitbox {}", attack.name, self.preview_hitbox_index + 1))
+    }
+
+    // One trajectory each for no DI, full DI in and full DI out. The dummy is launched the
+    // way the player is facing.
+    pub fn knockback_previews(&self, game: &FightingGame) -> Vec<KnockbackPreview> {
+        let hitbox = match self.preview_hitbox(game) {
+            Some(hitbox) => hitbox,
+            None => return Vec::new(),
+        };
+        let (player, dummy) = match (game.players.get(self.player_index), game.players.get(self.dummy_index)) {
+            (Some(player), Some(dummy)) => (&player.fighter, &dummy.fighter),
+            _ => return Vec::new(),
+        };
+
+        let mut knockback = knockback::calculate_knockback(hitbox, dummy.percent, dummy.attributes.weight);
+        if dummy.is_crouching() {
+            knockback *= 2.0 / 3.0;
+        }
+        let launch_velocity = knockback::calculate_launch_velocity(knockback, hitbox.angle, player.facing_direction());
+        let toward_player = if player.position.x >= dummy.position.x { 1.0 } else { -1.0 };
+
+        [DIBehavior::None, DIBehavior::In, DIBehavior::Out].iter()
+            .map(|di_behavior| {
+                let stick_x = match di_behavior {
+                    DIBehavior::In => toward_player,
+                    DIBehavior::Out => -toward_player,
+                    _ => 0.0,
+                };
+                let di_launch_velocity = knockback::apply_di(&launch_velocity, &Vector { x: stick_x, y: 0.0 });
+                KnockbackPreview {
+                    di_behavior: *di_behavior,
+                    trajectory: knockback::simulate_trajectory(
+                        &dummy.position,
+                        &di_launch_velocity,
+                        dummy.attributes.gravity,
+                        dummy.attributes.fall_velocity,
+                        &game.stage.blast_zone,
+                        300,
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    // Builds the controller the dummy plays with this frame.
+    fn update_dummy_input(&mut self, dummy: &Fighter, opponent_x: f64) {
+        let was_jumping = self.dummy_input.x_button.is_pressed;
+        self.dummy_input = ControllerState::default();
+        let toward_opponent = if opponent_x >= dummy.position.x { 1.0 } else { -1.0 };
+
+        match self.dummy_behavior {
+            DummyBehavior::Stand => (),
+
+            // Far enough down to crouch, but not a smash so it doesn't drop through platforms.
+            DummyBehavior::Crouch => self.dummy_input.y_axis.value = -0.65,
+
+            // Jump again as soon as it's back on the ground.
+            DummyBehavior::Jump => self.dummy_input.x_button.is_pressed = !was_jumping && !dummy.is_airborne(),
+
+            // Let go in the air so the dummy doesn't air dodge.
+            DummyBehavior::Shield => self.dummy_input.r_button.is_pressed = !dummy.is_airborne(),
+        }
+
+        // Whether to tech is picked once per hit. Pressing L every other frame of hitstun
+        // keeps the tech window open for whenever the dummy lands.
+        if dummy.state == FighterState::Hitstun {
+            if dummy.state_frame == 0 {
+                self.should_tech = match self.tech_behavior {
+                    TechBehavior::None => false,
+                    TechBehavior::InPlace => true,
+                    TechBehavior::Random => self.next_random().is_multiple_of(2),
+                };
+            }
+            self.dummy_input.l_button.is_pressed = self.should_tech && dummy.state_frame.is_multiple_of(2);
+        }
+
+        // DI is held through hitstun, but only the stick on the first frame of it counts.
+        // Random DI picks a direction for each hit.
+        if dummy.state == FighterState::Hitstun {
+            if dummy.state_frame == 0 {
+                self.random_di_direction = match self.next_random() % 3 {
+                    0 => -1.0,
+                    1 => 0.0,
+                    _ => 1.0,
+                };
+            }
+            self.dummy_input.x_axis.value = match self.di_behavior {
+                DIBehavior::None => self.dummy_input.x_axis.value,
+                DIBehavior::In => toward_opponent,
+                DIBehavior::Out => -toward_opponent,
+                DIBehavior::Random => self.random_di_direction,
+            };
+        }
+    }
+
+    // A small xorshift generator so random choices don't need another dependency.
+    fn next_random(&mut self) -> u64 {
+        self.random_state ^= self.random_state << 13;
+        self.random_state ^= self.random_state >> 7;
+        self.random_state ^= self.random_state << 17;
+        self.random_state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::fighter::FighterState;
+    use crate::match_rules::MatchRules;
+    use crate::stage::Stage;
+
+    fn training_game() -> FightingGame {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join("stages").join("final_destination.json");
+        let stage = Stage::from_file(&path).unwrap();
+        let mut game = FightingGame::new(MatchRules::default(), stage, vec![Fighter::fox(), Fighter::fox()]);
+        game.change_match_state(MatchState::Playing);
+        game
+    }
+
+    // Holding L and then pressing right changes the DI setting without shielding, and L
+    // stays hidden from the fighter until it is let go.
+    #[test]
+    fn settings_chords_dont_reach_the_fighter() {
+        let mut game = training_game();
+        let mut rewind = Rewind::default();
+        let mut training_mode = TrainingMode::default();
+
+        let mut input = ControllerState::default();
+        input.l_button.is_pressed = true;
+        input.d_right_button.is_pressed = true;
+        training_mode.update(&mut game, &mut rewind, &input);
+        assert_eq!(training_mode.di_behavior, DIBehavior::In);
+        assert_eq!(game.players[0].fighter.state, FighterState::Idle);
+
+        input.d_right_button.is_pressed = false;
+        for _ in 0..10 {
+            training_mode.update(&mut game, &mut rewind, &input);
+            assert_eq!(game.players[0].fighter.state, FighterState::Idle);
+        }
+
+        input.l_button.is_pressed = false;
+        training_mode.update(&mut game, &mut rewind, &input);
+        input.l_button.is_pressed = true;
+        training_mode.update(&mut game, &mut rewind, &input);
+        assert_eq!(game.players[0].fighter.state, FighterState::Shield);
+    }
+
+    // Player 1 up smashes the dummy from up close. Each DI setting launches the dummy
+    // along the knockback preview for that setting, and DI in and out go different ways.
+    #[test]
+    fn dummy_di_follows_the_preview() {
+        let mut final_positions = Vec::new();
+        for di_behavior in [DIBehavior::None, DIBehavior::In, DIBehavior::Out] {
+            let mut game = training_game();
+            let defender_x = game.players[0].fighter.position.x + 5.0;
+            game.players[1].fighter.position.x = defender_x;
+            game.players[1].fighter.previous_position.x = defender_x;
+            game.players[1].fighter.percent = 50.0;
+            let mut rewind = Rewind::default();
+            let mut training_mode = TrainingMode::default();
+            training_mode.di_behavior = di_behavior;
+            training_mode.preview_attack_index = 1;
+
+            let mut input = ControllerState::default();
+            input.a_button.is_pressed = true;
+            let mut possible_trajectory = None;
+            while game.players[1].fighter.state != FighterState::Hitstun {
+                assert!(game.match_frame < 60, "the up smash missed");
+                possible_trajectory = training_mode.knockback_previews(&game).into_iter()
+                    .find(|preview| preview.di_behavior == di_behavior)
+                    .map(|preview| preview.trajectory);
+                training_mode.update(&mut game, &mut rewind, &input);
+            }
+
+            let trajectory = possible_trajectory.unwrap();
+            let ground_y = trajectory.points[0].y;
+            input.a_button.is_pressed = false;
+            for point in trajectory.points.iter().skip(1).take_while(|point| point.y > ground_y) {
+                training_mode.update(&mut game, &mut rewind, &input);
+                let position = &game.players[1].fighter.position;
+                assert!((position.x - point.x).abs() < 1e-9 && (position.y - point.y).abs() < 1e-9,
+                    "{:?} DI at ({}, {}) instead of ({}, {})", di_behavior, position.x, position.y, point.x, point.y);
+            }
+            final_positions.push(game.players[1].fighter.position.x);
+        }
+
+        // Player 1 is to the left, so DI in goes left of no DI and DI out goes right.
+        assert!(final_positions[1] < final_positions[0]);
+        assert!(final_positions[2] > final_positions[0]);
+    }
+}
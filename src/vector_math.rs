@@ -55,11 +55,6 @@ impl Vector {
         self.x * other_vector.x + self.y * other_vector.y
     }
 
-    pub fn invert(&mut self) {
-        self.x = -self.x;
-        self.y = -self.y;
-    }
-
     pub fn inverse(&self) -> Self {
         Self {
             x: -self.x,